@@ -0,0 +1,84 @@
+//! Marks, named bookmarks and the jump list.
+//!
+//! A mark is a bookmark whose name is a single character, set with `m<char>` and jumped to with `'<char>`.
+//! Bookmarks added from the bookmark panel can have longer names and a comment.
+//! Jumps made by search, go-to and bookmarks are remembered in a `JumpList`, so the cursor can walk back and
+//! forward through them like a browser's history.
+
+/// Most positions the jump list remembers in each direction.
+const JUMP_LIST_LIMIT: usize = 100;
+
+pub struct Bookmark {
+    pub name: String,
+    pub pos: usize,
+    pub comment: String,
+}
+
+/// Every bookmark in a file, kept in order of position.
+#[derive(Default)]
+pub struct Bookmarks {
+    list: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    pub fn list(&self) -> &[Bookmark] {
+        &self.list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.list.iter().find(|b| b.name == name)
+    }
+
+    /// Add a bookmark, replacing any existing one with the same name.
+    pub fn set(&mut self, name: &str, pos: usize, comment: String) {
+        self.list.retain(|b| b.name != name);
+        let index = self.list.partition_point(|b| b.pos <= pos);
+        self.list.insert(index, Bookmark { name: name.to_string(), pos, comment });
+    }
+
+    pub fn set_comment(&mut self, index: usize, comment: String) {
+        self.list[index].comment = comment;
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.list.remove(index);
+    }
+}
+
+/// Positions jumped away from (`back`) and, after going back, positions to return to (`forward`).
+#[derive(Default)]
+pub struct JumpList {
+    back: Vec<usize>,
+    forward: Vec<usize>,
+}
+
+impl JumpList {
+    /// Remember `from` before jumping away from it. A new jump forgets anything that could be gone forward to.
+    pub fn record(&mut self, from: usize) {
+        if self.back.last() != Some(&from) {
+            self.back.push(from);
+            if self.back.len() > JUMP_LIST_LIMIT {
+                self.back.remove(0);
+            }
+        }
+        self.forward.clear();
+    }
+
+    /// Where to go back to from `current`, if anywhere.
+    pub fn back(&mut self, current: usize) -> Option<usize> {
+        let pos = self.back.pop()?;
+        self.forward.push(current);
+        Some(pos)
+    }
+
+    /// Where to go forward to from `current`, if anywhere.
+    pub fn forward(&mut self, current: usize) -> Option<usize> {
+        let pos = self.forward.pop()?;
+        self.back.push(current);
+        Some(pos)
+    }
+}
@@ -0,0 +1,415 @@
+//! Piece-table byte buffer.
+//!
+//! Stores the file as the untouched original data plus an append-only "added" buffer, with a list of
+//! pieces describing which spans of each make up the current contents. Inserts and deletes only touch
+//! the piece list, so editing the middle of a huge file never copies the file itself.
+//! Large files are not loaded at all: their original data is read page by page through `PagedFile`.
+
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::paged::PagedFile;
+
+/// Files up to this size are read into memory up front; anything larger is paged in lazily.
+const LAZY_LOAD_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Where the original (unedited) file data lives.
+enum Original {
+    Memory(Vec<u8>),
+    File(PagedFile),
+}
+
+impl Original {
+    fn len(&self) -> usize {
+        match self {
+            Original::Memory(data) => data.len(),
+            Original::File(file) => file.len(),
+        }
+    }
+
+    fn read_into(&self, pos: usize, out: &mut [u8]) {
+        match self {
+            Original::Memory(data) => out.copy_from_slice(&data[pos..pos + out.len()]),
+            Original::File(file) => file.read_into(pos, out),
+        }
+    }
+
+    fn copy_to<W: Write>(&self, pos: usize, len: usize, w: &mut W) -> io::Result<()> {
+        match self {
+            Original::Memory(data) => w.write_all(&data[pos..pos + len]),
+            Original::File(file) => file.copy_to(pos, len, w),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    Original,
+    Added,
+}
+
+/// A span of bytes taken from one of the two backing buffers.
+#[derive(Clone, Copy)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+}
+
+/// A stretch where the buffer differs from the original data: the `original` range of the original data
+/// was replaced by the `current` range of the buffer. One side is empty for a plain insert or delete.
+pub struct Change {
+    pub original: Range<usize>,
+    pub current: Range<usize>,
+}
+
+#[derive(Clone)]
+pub struct PieceTable {
+    original: Rc<Original>, // File contents as loaded, never modified (shared so clones stay cheap)
+    added: Vec<u8>,     // Every byte ever inserted, append-only
+    pieces: Vec<Piece>,
+    len: usize,
+}
+
+impl PieceTable {
+    /// Open `path` as a buffer, reading small files into memory and paging large ones in on demand.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let original = if fs::metadata(path)?.len() <= LAZY_LOAD_THRESHOLD {
+            Original::Memory(fs::read(path)?)
+        } else {
+            Original::File(PagedFile::open(path)?)
+        };
+        Ok(Self::from_original(original))
+    }
+
+    fn from_original(original: Original) -> Self {
+        let len = original.len();
+        let pieces = if len > 0 {
+            vec![Piece { source: Source::Original, start: 0, len }]
+        } else {
+            Vec::new()
+        };
+        Self {
+            original: Rc::new(original),
+            added: Vec::new(),
+            pieces,
+            len,
+        }
+    }
+
+    /// A buffer holding `data`, for tests that don't want to go through a file.
+    #[cfg(test)]
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self::from_original(Original::Memory(data.to_vec()))
+    }
+
+    /// Logical length of the buffer in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the byte at `pos`, or None if out of range.
+    pub fn get(&self, pos: usize) -> Option<u8> {
+        let (idx, off) = self.locate(pos);
+        let piece = self.pieces.get(idx)?;
+        let mut byte = [0];
+        self.read_piece(piece, off, &mut byte);
+        Some(byte[0])
+    }
+
+    /// Get the byte that was at `pos` in the original data, ignoring all edits.
+    pub fn original_byte(&self, pos: usize) -> Option<u8> {
+        if pos >= self.original.len() {
+            return None;
+        }
+        let mut byte = [0];
+        self.original.read_into(pos, &mut byte);
+        Some(byte[0])
+    }
+
+    /// Copy the bytes in `range` of the original data (clamped to its length) into a new Vec, ignoring all edits.
+    pub fn original_range(&self, range: Range<usize>) -> Vec<u8> {
+        let end = range.end.min(self.original.len());
+        let mut out = vec![0; end.saturating_sub(range.start)];
+        if !out.is_empty() {
+            self.original.read_into(range.start, &mut out);
+        }
+        out
+    }
+
+    /// Copy the bytes in `range` (clamped to the buffer length) into a new Vec.
+    pub fn read_range(&self, range: Range<usize>) -> Vec<u8> {
+        let end = range.end.min(self.len);
+        let mut out = vec![0; end.saturating_sub(range.start)];
+        let mut piece_start = 0;
+        for piece in &self.pieces {
+            let piece_end = piece_start + piece.len;
+            if piece_end > range.start && piece_start < end {
+                let from = range.start.max(piece_start);
+                let to = end.min(piece_end);
+                self.read_piece(piece, from - piece_start, &mut out[from - range.start..to - range.start]);
+            }
+            if piece_end >= end {
+                break;
+            }
+            piece_start = piece_end;
+        }
+        out
+    }
+
+    /// Insert `data` before `pos`. `pos == len()` appends.
+    pub fn insert(&mut self, pos: usize, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let pos = pos.min(self.len);
+        let start = self.added.len();
+        self.added.extend_from_slice(data);
+        let new_piece = Piece { source: Source::Added, start, len: data.len() };
+        self.len += data.len();
+
+        let (idx, off) = self.locate(pos);
+        if off == 0 {
+            // Typing sequentially keeps appending to the added buffer, so just grow the previous piece
+            if idx > 0 {
+                let prev = &mut self.pieces[idx - 1];
+                if prev.source == Source::Added && prev.start + prev.len == start {
+                    prev.len += data.len();
+                    return;
+                }
+            }
+            self.pieces.insert(idx, new_piece);
+        } else {
+            // Split the piece we landed in around the new one
+            let piece = self.pieces[idx];
+            let left = Piece { len: off, ..piece };
+            let right = Piece { start: piece.start + off, len: piece.len - off, ..piece };
+            self.pieces.splice(idx..=idx, [left, new_piece, right]);
+        }
+    }
+
+    /// Remove `count` bytes starting at `pos` (clamped to the end of the buffer).
+    pub fn delete(&mut self, pos: usize, count: usize) {
+        let end = (pos + count).min(self.len);
+        if pos >= end {
+            return;
+        }
+        let (first, first_off) = self.locate(pos);
+        let (last, last_off) = self.locate(end);
+
+        // Keep whatever is left of the first and last pieces the range cuts into
+        let mut kept = Vec::with_capacity(2);
+        if first_off > 0 {
+            kept.push(Piece { len: first_off, ..self.pieces[first] });
+        }
+        let remove_end = if last_off > 0 {
+            let piece = self.pieces[last];
+            kept.push(Piece { start: piece.start + last_off, len: piece.len - last_off, ..piece });
+            last + 1
+        } else {
+            last
+        };
+        self.pieces.splice(first..remove_end, kept);
+        self.len -= end - pos;
+    }
+
+    /// Overwrite bytes starting at `pos` with `data`, growing the buffer if it runs past the end.
+    pub fn replace(&mut self, pos: usize, data: &[u8]) {
+        let overlap = data.len().min(self.len.saturating_sub(pos));
+        self.delete(pos, overlap);
+        self.insert(pos, data);
+    }
+
+    /// Every place the buffer differs from the original data, in order.
+    /// Original pieces always stay in file order, so anything between two of them that doesn't continue
+    /// straight on from the first is a change. Bytes that were edited back to their original value don't count.
+    pub fn changes(&self) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let mut original_end = 0; // Where the last original piece ended, in the original data
+        let mut unchanged_end = 0; // Where it ended in the buffer
+        let mut pos = 0;
+        for piece in &self.pieces {
+            if piece.source == Source::Original {
+                if piece.start != original_end || pos != unchanged_end {
+                    self.push_change(&mut changes, original_end..piece.start, unchanged_end..pos);
+                }
+                original_end = piece.start + piece.len;
+                unchanged_end = pos + piece.len;
+            }
+            pos += piece.len;
+        }
+        if original_end != self.original.len() || pos != unchanged_end {
+            self.push_change(&mut changes, original_end..self.original.len(), unchanged_end..pos);
+        }
+        changes
+    }
+
+    /// Record a change, trimming off any bytes at either end that are the same on both sides.
+    fn push_change(&self, changes: &mut Vec<Change>, mut original: Range<usize>, mut current: Range<usize>) {
+        if !original.is_empty() && !current.is_empty() {
+            let old = self.original_range(original.clone());
+            let new = self.read_range(current.clone());
+            let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+            let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+            original = original.start + prefix..original.end - suffix;
+            current = current.start + prefix..current.end - suffix;
+        }
+        if !original.is_empty() || !current.is_empty() {
+            changes.push(Change { original, current });
+        }
+    }
+
+    /// Write the whole buffer to `w`, one piece at a time.
+    /// Original data is streamed from disk, so unmodified regions are never held in memory.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for piece in &self.pieces {
+            match piece.source {
+                Source::Original => self.original.copy_to(piece.start, piece.len, w)?,
+                Source::Added => w.write_all(&self.added[piece.start..piece.start + piece.len])?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill `out` with bytes from `piece`, starting `off` bytes into it.
+    fn read_piece(&self, piece: &Piece, off: usize, out: &mut [u8]) {
+        let start = piece.start + off;
+        match piece.source {
+            Source::Original => self.original.read_into(start, out),
+            Source::Added => out.copy_from_slice(&self.added[start..start + out.len()]),
+        }
+    }
+
+    /// Find which piece holds `pos` and how far into it `pos` is.
+    /// Returns `(pieces.len(), 0)` when `pos` is at or past the end.
+    fn locate(&self, pos: usize) -> (usize, usize) {
+        let mut piece_start = 0;
+        for (i, piece) in self.pieces.iter().enumerate() {
+            if pos < piece_start + piece.len {
+                return (i, pos - piece_start);
+            }
+            piece_start += piece.len;
+        }
+        (self.pieces.len(), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(table: &PieceTable) -> Vec<u8> {
+        let mut out = Vec::new();
+        table.write_to(&mut out).unwrap();
+        assert_eq!(out, table.read_range(0..table.len()));
+        out
+    }
+
+    fn changes(table: &PieceTable) -> Vec<(Range<usize>, Range<usize>)> {
+        table.changes().into_iter().map(|change| (change.original, change.current)).collect()
+    }
+
+    #[test]
+    fn edits_match_a_plain_vec() {
+        // Random inserts, deletes and overwrites, checked against the same edits made to a Vec
+        let mut table = PieceTable::from_bytes(&(0..=255).collect::<Vec<u8>>());
+        let mut model: Vec<u8> = (0..=255).collect();
+        let mut seed = 1u32;
+        let mut random = |below: usize| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as usize % below.max(1)
+        };
+        for step in 0..500 {
+            let pos = random(model.len() + 1);
+            let data: Vec<u8> = (0..random(8) + 1).map(|i| (step + i) as u8).collect();
+            match random(3) {
+                0 => {
+                    table.insert(pos, &data);
+                    model.splice(pos..pos, data);
+                }
+                1 => {
+                    let count = random(10);
+                    table.delete(pos, count);
+                    model.drain(pos..(pos + count).min(model.len()));
+                }
+                _ => {
+                    table.replace(pos, &data);
+                    let end = (pos + data.len()).min(model.len());
+                    model.splice(pos..end, data);
+                }
+            }
+            assert_eq!(table.len(), model.len());
+        }
+        assert_eq!(contents(&table), model);
+        for pos in [0, 1, model.len() / 2, model.len() - 1] {
+            assert_eq!(table.get(pos), Some(model[pos]));
+        }
+        assert_eq!(table.get(model.len()), None);
+        assert_eq!(table.read_range(10..20), model[10..20]);
+        assert_eq!(table.read_range(model.len() - 2..model.len() + 5), model[model.len() - 2..]);
+    }
+
+    #[test]
+    fn original_data_is_never_touched() {
+        let mut table = PieceTable::from_bytes(b"hello world");
+        table.delete(0, 6);
+        table.insert(0, b"goodbye ");
+        assert_eq!(contents(&table), b"goodbye world");
+        assert_eq!(table.original_range(0..5), b"hello");
+        assert_eq!(table.original_byte(10), Some(b'd'));
+        assert_eq!(table.original_byte(11), None);
+    }
+
+    #[test]
+    fn changes_list_only_what_differs() {
+        let mut table = PieceTable::from_bytes(b"abcdefgh");
+        assert!(changes(&table).is_empty());
+
+        table.replace(2, b"XY");
+        table.insert(8, b"!!");
+        table.delete(0, 1);
+        assert_eq!(contents(&table), b"bXYefgh!!");
+        assert_eq!(changes(&table), [(0..1, 0..0), (2..4, 1..3), (8..8, 7..9)]);
+
+        // Writing the original bytes back isn't a change
+        table.replace(1, b"cd");
+        assert_eq!(changes(&table), [(0..1, 0..0), (8..8, 7..9)]);
+    }
+
+    #[test]
+    fn paged_files_read_like_memory() {
+        // Spans several pages, so reads and edits cross page boundaries
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let path = std::env::temp_dir().join(format!("microhex-paged-test-{}", std::process::id()));
+        fs::write(&path, &data).unwrap();
+        let mut paged = PieceTable::from_original(Original::File(PagedFile::open(&path).unwrap()));
+        let mut memory = PieceTable::from_bytes(&data);
+        for table in [&mut paged, &mut memory] {
+            table.replace(65_530, b"across a page boundary");
+            table.delete(131_000, 1_000);
+            table.insert(10, b"inserted");
+        }
+        assert_eq!(paged.len(), memory.len());
+        assert_eq!(paged.read_range(65_000..140_000), memory.read_range(65_000..140_000));
+        assert_eq!(paged.original_byte(199_999), Some(data[199_999]));
+        assert_eq!(contents(&paged), contents(&memory));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_buffers_can_be_filled() {
+        let mut table = PieceTable::from_bytes(b"");
+        assert!(table.is_empty());
+        table.replace(0, b"abc");
+        table.insert(100, b"d");
+        assert_eq!(contents(&table), b"abcd");
+        assert_eq!(changes(&table), [(0..0, 0..4)]);
+    }
+}
@@ -0,0 +1,373 @@
+//! Clipboard text formats and the system clipboard.
+//!
+//! The clipboard itself is just bytes, shared by every buffer in the `Workspace`. Text typed or pasted into
+//! microhex is turned into bytes here: a hex string (`de ad be ef`), a C array (`{0xde, 0xad}`), a Python
+//! bytes literal (`b'\xde\xad'`), base64 (padded, or too long to be a word) or, failing those, the text's
+//! own UTF-8 bytes. A `hex:`, `c:`, `python:`, `base64:` or `text:` prefix picks the format explicitly.
+//!
+//! Copied bytes also go to the system clipboard as text, using the OSC 52 escape sequence. The terminal
+//! does the copying, so it reaches the local machine's clipboard even over SSH.
+
+use std::io::{self, Write};
+use serde::Deserialize;
+
+/// Largest OSC 52 payload (after base64) to send. Terminals ignore or truncate much more than this, and writing
+/// megabytes of escape sequence to the terminal would stall it anyway.
+const MAX_SYSTEM_COPY: usize = 100_000;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Hex,
+    CArray,
+    Python,
+    Base64,
+    Raw,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Hex => "hex",
+            Format::CArray => "C array",
+            Format::Python => "Python bytes",
+            Format::Base64 => "base64",
+            Format::Raw => "raw text",
+        }
+    }
+}
+
+/// Prefixes that pick a format instead of leaving it to detection.
+const PREFIXES: [(&str, Format); 5] = [
+    ("hex:", Format::Hex),
+    ("c:", Format::CArray),
+    ("python:", Format::Python),
+    ("base64:", Format::Base64),
+    ("text:", Format::Raw),
+];
+
+/// True if `text` starts with a prefix naming its format, like `text:` or `base64:`.
+pub fn has_format_prefix(text: &str) -> bool {
+    PREFIXES.iter().any(|(prefix, _)| text.starts_with(prefix))
+}
+
+/// Bytes described by `text`, and which format it was read as.
+pub fn parse_text(text: &str) -> Result<(Vec<u8>, Format), String> {
+    for (prefix, format) in PREFIXES {
+        if let Some(rest) = text.strip_prefix(prefix) {
+            let bytes = match format {
+                Format::Hex => parse_hex(rest).ok_or("Expected pairs of hex digits")?,
+                Format::CArray => parse_c_array(rest).ok_or("Expected a list of byte values like {0xde, 0xad}")?,
+                Format::Python => parse_python_bytes(rest.trim()).ok_or("Expected a bytes literal like b'\\xde\\xad'")?,
+                Format::Base64 => decode_base64(rest).ok_or("Invalid base64")?,
+                Format::Raw => rest.as_bytes().to_vec(),
+            };
+            return Ok((bytes, format));
+        }
+    }
+
+    // Commas mean a list of values; without them, hex is far more likely than base64 for short strings, and
+    // plenty of ordinary words are valid base64 too, so that needs padding or a length no word has
+    let trimmed = text.trim();
+    if (trimmed.starts_with('{') || trimmed.contains(','))
+        && let Some(bytes) = parse_c_array(trimmed)
+    {
+        return Ok((bytes, Format::CArray));
+    }
+    if let Some(bytes) = parse_python_bytes(trimmed) {
+        return Ok((bytes, Format::Python));
+    }
+    if let Some(bytes) = parse_hex(trimmed) {
+        return Ok((bytes, Format::Hex));
+    }
+    if looks_like_base64(trimmed)
+        && let Some(bytes) = decode_base64(trimmed)
+    {
+        return Ok((bytes, Format::Base64));
+    }
+    Ok((text.as_bytes().to_vec(), Format::Raw))
+}
+
+/// Hex digit pairs, optionally separated by whitespace, `:`, `-` or commas, each group optionally prefixed
+/// with `0x` or `\x`.
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let mut digits = String::new();
+    for token in text.split(|c: char| c.is_whitespace() || matches!(c, ':' | '-' | ',')) {
+        for part in token.split("\\x") {
+            let part = part.strip_prefix("0x").or_else(|| part.strip_prefix("0X")).unwrap_or(part);
+            digits.push_str(part);
+        }
+    }
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok()).collect()
+}
+
+/// Comma-separated byte values, optionally inside braces (anything before the `{`, such as a declaration,
+/// is ignored). Values can be decimal, `0x` hex, `0b` binary, octal with a leading `0`, or character
+/// literals like `'A'`.
+fn parse_c_array(text: &str) -> Option<Vec<u8>> {
+    let body = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start + 1..end],
+        (None, None) => text,
+        _ => return None,
+    };
+    let values: Vec<&str> = body.split(',').map(str::trim).collect();
+    // A trailing comma is allowed, as in C
+    let values = match values.split_last() {
+        Some((&"", rest)) => rest,
+        _ => &values[..],
+    };
+    if values.is_empty() {
+        return None;
+    }
+    values.iter().map(|value| parse_c_value(value)).collect()
+}
+
+fn parse_c_value(value: &str) -> Option<u8> {
+    if let Some(c) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        let mut chars = c.chars();
+        return match (chars.next()?, chars.next()) {
+            (c, None) => u8::try_from(c as u32).ok(),
+            ('\\', Some(escaped)) => match escaped {
+                'n' => Some(b'\n'),
+                'r' => Some(b'\r'),
+                't' => Some(b'\t'),
+                '0' => Some(0),
+                '\\' | '\'' | '"' => Some(escaped as u8),
+                _ => None,
+            },
+            _ => None,
+        };
+    }
+    let lower = value.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = lower.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        (binary, 2)
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        (&lower[1..], 8)
+    } else {
+        (&lower[..], 10)
+    };
+    u8::from_str_radix(digits, radix).ok()
+}
+
+/// A Python bytes literal such as `b'AB\x00\n'`, in single or double quotes.
+fn parse_python_bytes(text: &str) -> Option<Vec<u8>> {
+    let quoted = text.strip_prefix('b').or_else(|| text.strip_prefix('B'))?;
+    let quote = quoted.chars().next().filter(|&q| q == '\'' || q == '"')?;
+    let body = quoted[1..].strip_suffix(quote)?;
+    let mut bytes = Vec::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.push(u8::try_from(c).ok().filter(u8::is_ascii)?);
+            continue;
+        }
+        bytes.push(match chars.next()? {
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&digits, 16).ok()?
+            }
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => 0,
+            escaped @ ('\\' | '\'' | '"') => escaped as u8,
+            _ => return None,
+        });
+    }
+    Some(bytes)
+}
+
+/// True if `text` is unlikely to be anything but base64: a whole number of 4-character blocks (line breaks
+/// aside) with no spaces, that either ends in `=` padding or is too long to be a word.
+fn looks_like_base64(text: &str) -> bool {
+    const MIN_UNPADDED_LEN: usize = 32;
+    let chars: String = text.chars().filter(|c| !matches!(c, '\n' | '\r')).collect();
+    !chars.contains(' ')
+        && chars.len().is_multiple_of(4)
+        && (chars.ends_with('=') || chars.len() >= MIN_UNPADDED_LEN)
+}
+
+/// Standard base64, with or without padding. Whitespace (such as line breaks) is ignored.
+pub fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let chars: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let data = chars.strip_suffix(b"==").or_else(|| chars.strip_suffix(b"=")).unwrap_or(&chars);
+    if data.is_empty() || data.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &c in data {
+        let value = BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Standard base64 with padding.
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, &b)| buffer | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[(buffer >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// How copied bytes are written out for the system clipboard.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyFormat {
+    None, // Don't touch the system clipboard
+    #[default]
+    Hex,    // de ad be ef
+    C,      // {0xde, 0xad, 0xbe, 0xef}
+    Python, // b'\xde\xad\xbe\xef'
+    Base64, // 3q2+7w==
+}
+
+impl CopyFormat {
+    /// `bytes` as text in this format, or None if nothing should be copied.
+    pub fn format(self, bytes: &[u8]) -> Option<String> {
+        let hex = |b: &u8| format!("0x{b:02x}");
+        Some(match self {
+            CopyFormat::None => return None,
+            CopyFormat::Hex => bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" "),
+            CopyFormat::C => format!("{{{}}}", bytes.iter().map(hex).collect::<Vec<_>>().join(", ")),
+            CopyFormat::Python => {
+                let mut text = String::from("b'");
+                for &b in bytes {
+                    match b {
+                        b'\\' | b'\'' => text.extend(['\\', b as char]),
+                        b'\n' => text.push_str("\\n"),
+                        b'\r' => text.push_str("\\r"),
+                        b'\t' => text.push_str("\\t"),
+                        0x20..=0x7e => text.push(b as char),
+                        _ => text.push_str(&format!("\\x{b:02x}")),
+                    }
+                }
+                text.push('\'');
+                text
+            }
+            CopyFormat::Base64 => encode_base64(bytes),
+        })
+    }
+}
+
+/// Ask the terminal to put `text` on the system clipboard with the OSC 52 escape sequence. Terminals that
+/// don't support it (or have it turned off) just ignore it, and there's no way to tell whether it worked.
+/// Returns false, without sending anything, if `text` is too long to send.
+pub fn copy_to_system(text: &str) -> io::Result<bool> {
+    if text.len().div_ceil(3) * 4 > MAX_SYSTEM_COPY {
+        return Ok(false);
+    }
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encode_base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (Vec<u8>, Format) {
+        parse_text(text).unwrap()
+    }
+
+    #[test]
+    fn large_copies_skip_the_system_clipboard() {
+        let text = "a".repeat(MAX_SYSTEM_COPY);
+        assert!(!copy_to_system(&text).unwrap());
+    }
+
+    #[test]
+    fn detects_hex_strings() {
+        assert_eq!(parse("de ad be ef"), (vec![0xde, 0xad, 0xbe, 0xef], Format::Hex));
+        assert_eq!(parse("deadbeef"), (vec![0xde, 0xad, 0xbe, 0xef], Format::Hex));
+        assert_eq!(parse("0xde 0xad"), (vec![0xde, 0xad], Format::Hex));
+        assert_eq!(parse("\\xde\\xad"), (vec![0xde, 0xad], Format::Hex));
+        assert_eq!(parse("de:ad-be"), (vec![0xde, 0xad, 0xbe], Format::Hex));
+    }
+
+    #[test]
+    fn detects_c_arrays() {
+        assert_eq!(parse("{0xde, 0xad}"), (vec![0xde, 0xad], Format::CArray));
+        assert_eq!(
+            parse("unsigned char key[] = {1, 0x02, 0b11, 010, 'A', '\\n',};"),
+            (vec![1, 2, 3, 8, b'A', b'\n'], Format::CArray)
+        );
+        // Comma-separated values are decimal, not hex pairs
+        assert_eq!(parse("10, 20"), (vec![10, 20], Format::CArray));
+        // ...unless they can only be hex
+        assert_eq!(parse("de, ad"), (vec![0xde, 0xad], Format::Hex));
+    }
+
+    #[test]
+    fn detects_python_bytes() {
+        assert_eq!(parse(r"b'AB\x00\n'"), (vec![b'A', b'B', 0, b'\n'], Format::Python));
+        assert_eq!(parse(r#"b"it's""#), (b"it's".to_vec(), Format::Python));
+    }
+
+    #[test]
+    fn detects_base64_only_when_unambiguous() {
+        assert_eq!(parse("3q2+7w=="), (vec![0xde, 0xad, 0xbe, 0xef], Format::Base64));
+        let long = encode_base64(&[0xab; 24]);
+        assert_eq!(parse(&long), (vec![0xab; 24], Format::Base64));
+        // Ordinary words that happen to be valid base64 stay text
+        for word in ["test", "hi", "abc", "word", "this is some text"] {
+            assert_eq!(parse(word), (word.as_bytes().to_vec(), Format::Raw), "{word}");
+        }
+    }
+
+    #[test]
+    fn explicit_prefixes_override_detection() {
+        assert_eq!(parse("text:deadbeef"), (b"deadbeef".to_vec(), Format::Raw));
+        assert_eq!(parse("base64:dGVzdA"), (b"test".to_vec(), Format::Base64));
+        assert_eq!(parse("hex:41"), (vec![0x41], Format::Hex));
+        assert!(parse_text("hex:xyz").is_err());
+        assert!(parse_text("c:{300}").is_err());
+    }
+
+    #[test]
+    fn base64_round_trips() {
+        for len in 0..10 {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 37) as u8).collect();
+            let text = encode_base64(&bytes);
+            assert!(text.len().is_multiple_of(4));
+            if len > 0 {
+                assert_eq!(decode_base64(&text), Some(bytes));
+            }
+        }
+    }
+
+    #[test]
+    fn formats_copied_bytes() {
+        let bytes = [0xde, 0xad, b'A', b'\''];
+        assert_eq!(CopyFormat::Hex.format(&bytes).unwrap(), "de ad 41 27");
+        assert_eq!(CopyFormat::C.format(&bytes).unwrap(), "{0xde, 0xad, 0x41, 0x27}");
+        assert_eq!(CopyFormat::Python.format(&bytes).unwrap(), r"b'\xde\xadA\''");
+        assert!(CopyFormat::None.format(&bytes).is_none());
+        // Each text format reads back as the same bytes
+        for format in [CopyFormat::Hex, CopyFormat::C, CopyFormat::Python, CopyFormat::Base64] {
+            assert_eq!(parse_text(&format.format(&bytes).unwrap()).unwrap().0, bytes);
+        }
+    }
+}
@@ -1,11 +1,18 @@
 //! Configuration loading and data structures.
 //!
-//! Defines the `AppConfig` and `ColorConfig` structs, and loads configuration from a TOML file.
-//! Supports user-customizable color themes and is ready for future config options.
+//! Defines the `AppConfig`, `ColorConfig` and `EditorConfig` structs, and loads configuration from a TOML file.
+//! Supports user-customizable color themes and editor behaviour options.
 
 use serde::Deserialize;
 use std::{fs, path::Path};
 
+use crate::clipboard::CopyFormat;
+use crate::encoding::Encoding;
+use crate::save::BackupMode;
+
+/// Word sizes the hex pane can show. Lines are laid out in whole words, so nothing else lines up.
+pub const WORD_SIZES: [usize; 4] = [1, 2, 4, 8];
+
 #[derive(Deserialize, Clone)]
 pub struct ColorConfig {
     pub status_bg: u8,
@@ -21,11 +28,117 @@ pub struct ColorConfig {
     pub null_fg: u8,
     pub control_fg: u8,
     pub printable_fg: u8,
+    #[serde(default = "default_selection_bg")] // Added later; older config files don't have it
+    pub selection_bg: u8,
+}
+
+fn default_selection_bg() -> u8 {
+    24
+}
+
+/// Editor behaviour options. Every field has a default, so older config files without
+/// an `[editor]` section (or with only some of its keys) keep working.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct EditorConfig {
+    pub persistent_undo: bool, // Keep undo history in a sidecar file between sessions
+    pub swap_interval: u64,    // Seconds between swap file updates while there are unsaved edits (0 = off)
+    pub backup: BackupMode,    // What to keep of the previous version on save
+    pub watch_file: bool,      // Check for changes made by other programs while idle and offer to reload
+    pub save_bookmarks: bool,  // Keep marks and bookmarks in a sidecar file between sessions
+    pub bytes_per_line: usize, // Bytes shown on each line (0 = as many as fit the terminal)
+    pub group_size: usize,     // Extra space between every this many bytes (0 = none)
+    pub word_size: usize,      // Bytes shown together as one word in the hex pane (1 = single bytes)
+    pub endian: Endian,        // Order the bytes of a word are shown in
+    pub radix: Radix,          // Number base of the cells in the data pane
+    pub encoding: Encoding,    // Character encoding of the text pane
+    pub copy_format: CopyFormat, // How copied bytes are put on the system clipboard
+}
+
+/// Number base the data pane shows and edits bytes in.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Radix {
+    #[default]
+    Hex,
+    Binary,
+    Octal,
+    Decimal,
+}
+
+impl Radix {
+    pub fn base(self) -> u32 {
+        match self {
+            Radix::Hex => 16,
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+        }
+    }
+
+    /// Digits needed for any byte value, which is also the width of a cell.
+    pub fn digits(self) -> usize {
+        match self {
+            Radix::Hex => 2,
+            Radix::Binary => 8,
+            Radix::Octal | Radix::Decimal => 3,
+        }
+    }
+
+    /// `byte` written out in full, zero-padded to the width of a cell.
+    pub fn format(self, byte: u8) -> String {
+        match self {
+            Radix::Hex => format!("{byte:02x}"),
+            Radix::Binary => format!("{byte:08b}"),
+            Radix::Octal => format!("{byte:03o}"),
+            Radix::Decimal => format!("{byte:03}"),
+        }
+    }
+
+    /// Short name for the status line.
+    pub fn label(self) -> &'static str {
+        match self {
+            Radix::Hex => "HEX",
+            Radix::Binary => "BIN",
+            Radix::Octal => "OCT",
+            Radix::Decimal => "DEC",
+        }
+    }
+}
+
+/// Byte order used to show multi-byte words in the hex pane.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Big,    // Bytes in file order, most significant first when read as a number
+    Little, // Each word's bytes reversed, like `xxd -e`
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            persistent_undo: false,
+            swap_interval: 5,
+            backup: BackupMode::None,
+            watch_file: true,
+            save_bookmarks: false,
+            bytes_per_line: 16,
+            group_size: 8,
+            word_size: 1,
+            endian: Endian::Big,
+            radix: Radix::Hex,
+            encoding: Encoding::Ascii,
+            copy_format: CopyFormat::Hex,
+        }
+    }
 }
 
 #[derive(Deserialize, Clone)]
 pub struct AppConfig {
     pub colors: ColorConfig,
+    #[serde(default)]
+    pub editor: EditorConfig,
     // Add other config fields here in the future
 }
 
@@ -48,13 +161,56 @@ changed_fg = 208
 null_fg = 242
 control_fg = 33
 printable_fg = 34
+selection_bg = 24
+
+[editor]
+# Save undo history next to the file (.<name>.microhex-undo) on exit and restore it
+# when the same, unchanged file is opened again.
+persistent_undo = false
+# While there are unsaved edits, write them to a swap file (.<name>.microhex-swp) at most
+# this many seconds apart, so they can be recovered if microhex or the terminal dies. 0 disables it.
+swap_interval = 5
+# Keep the previous version of a file when saving over it:
+# "none", "simple" (file~) or "numbered" (file.~1~, file.~2~, ...)
+backup = "none"
+# Check every few seconds whether another program changed the file and offer to reload it.
+# Saving always warns about outside changes, whatever this is set to.
+watch_file = true
+# Save marks and bookmarks next to the file (.<name>.microhex-marks) on exit and restore them
+# when it's opened again.
+save_bookmarks = false
+# Bytes shown on each line, or fewer if they don't fit the terminal. 0 fits as many as the terminal width allows.
+# Can be overridden with --bytes-per-line <N|auto>.
+bytes_per_line = 16
+# Put an extra space between every this many bytes (0 = no gaps). Override with --group-size <N>.
+group_size = 8
+# Show the hex pane as words of this many bytes (1, 2, 4 or 8), like xxd -g. Alt+W cycles it.
+# Override with --word-size <N>.
+word_size = 1
+# Byte order within a word: "big" shows bytes in file order, "little" reverses each word like xxd -e.
+# Alt+E switches it. Override with --endian <big|little>.
+endian = "big"
+# Number base of the data pane: "hex", "binary", "octal" or "decimal". Alt+R cycles it.
+# Override with --radix <name>.
+radix = "hex"
+# Character encoding of the text pane, also used for typing in text edit mode: "ascii", "latin1",
+# "cp437", "cp037" (EBCDIC), "utf8", "utf16le" or "utf16be". Alt+N cycles it. Override with --encoding <name>.
+encoding = "ascii"
+# Copying or cutting a selection also puts it on the system clipboard, using the OSC 52 terminal escape
+# (this works over SSH, if the terminal allows it), written out as "hex" (de ad), "c" ({0xde, 0xad}),
+# "python" (b'\xde\xad') or "base64". "none" leaves the system clipboard alone. Override with --copy-format <name>.
+copy_format = "hex"
 "#;
         if !Path::new(path).exists() {
             let _ = fs::write(path, default_toml);
         }
-        fs::read_to_string(path)
+        let mut config: Self = fs::read_to_string(path)
             .ok()
             .and_then(|s| toml::from_str(&s).ok())
-            .unwrap_or_else(|| toml::from_str(default_toml).unwrap())
+            .unwrap_or_else(|| toml::from_str(default_toml).unwrap());
+        if !WORD_SIZES.contains(&config.editor.word_size) {
+            config.editor.word_size = 1;
+        }
+        config
     }
 }
\ No newline at end of file
@@ -0,0 +1,456 @@
+//! Side-by-side comparison of two files (`microhex --diff old.bin new.bin`).
+//!
+//! The files are split into `Hunk`s: ranges where they differ, separated by runs of equal bytes. By default
+//! bytes are compared offset by offset. With resync turned on, a greedy search realigns the files after an
+//! insertion or deletion, so everything after it isn't reported as different.
+//! Each pane is a read-only `MicroHex`; the active one is navigated as usual and the other follows it.
+
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+use crossterm::{
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, ClearType},
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+};
+
+use crate::{display, navigation};
+use crate::buffer::PieceTable;
+use crate::config::ColorConfig;
+use crate::editor::{ListAction, MicroHex};
+
+/// Bytes compared per read when scanning for differences.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// How many equal bytes in a row count as the files being back in step after a difference.
+const RESYNC_RUN: usize = 8;
+/// How far ahead in each file to look for the point where they line up again.
+const RESYNC_WINDOW: usize = 4096;
+
+/// A range of bytes in each file that don't match. One side is empty where bytes were only inserted
+/// into, or deleted from, the other file.
+pub struct Hunk {
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+}
+
+impl Hunk {
+    /// The range on pane `pane` (0 = left, 1 = right).
+    pub fn range(&self, pane: usize) -> &Range<usize> {
+        if pane == 0 { &self.left } else { &self.right }
+    }
+}
+
+pub struct DiffView {
+    pub panes: [MicroHex; 2],
+    pub active: usize,          // Pane being navigated; the other one follows it
+    pub resync: bool,           // Realign after insertions and deletions instead of comparing offset by offset
+    pub hunks: Vec<Hunk>,
+    pub current: Option<usize>, // Hunk last jumped to with next/previous difference
+}
+
+impl DiffView {
+    pub fn new(left: MicroHex, right: MicroHex) -> Self {
+        let mut view = Self {
+            panes: [left, right],
+            active: 0,
+            resync: false,
+            hunks: Vec::new(),
+            current: None,
+        };
+        view.compare();
+        view
+    }
+
+    pub fn run(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+
+        loop {
+            display::draw_diff(self, colors)?;
+            // Nothing to do while idle, but keep polling so a resize redraws promptly
+            if !event::poll(Duration::from_millis(500))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && self.handle_key_event(key, colors)?
+            {
+                break;
+            }
+        }
+
+        terminal::disable_raw_mode()?;
+        execute!(io::stdout(), cursor::Show, LeaveAlternateScreen)?;
+        Ok(())
+    }
+
+    /// Returns true when it's time to quit.
+    fn handle_key_event(&mut self, key: crossterm::event::KeyEvent, colors: &ColorConfig) -> io::Result<bool> {
+        let pane = &mut self.panes[self.active];
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+            KeyCode::Tab => {
+                self.active = 1 - self.active;
+                // Line the newly active pane back up on whole lines; the other one follows it
+                let pane = &mut self.panes[self.active];
+                pane.offset -= pane.offset % pane.bytes_per_line;
+                navigation::scroll_to_cursor(pane);
+            }
+            KeyCode::Char('n') => self.next_difference(),
+            KeyCode::Char('N') => self.prev_difference(),
+            KeyCode::Char('r') => {
+                self.resync = !self.resync;
+                self.compare();
+            }
+            KeyCode::Char('d') => {
+                let entries = self.summary();
+                let footer = "Up/Down: Select   Enter: Go to difference   Esc: Close";
+                let initial = self.current.unwrap_or(0);
+                if let Some(ListAction::Select(index)) =
+                    self.panes[self.active].select_from_list("Differences", &entries, initial, &[], footer, colors)?
+                {
+                    self.goto_hunk(index);
+                }
+            }
+            KeyCode::Up => navigation::move_up(pane),
+            KeyCode::Down => navigation::move_down(pane),
+            KeyCode::Left => navigation::move_left(pane),
+            KeyCode::Right => navigation::move_right(pane),
+            KeyCode::PageUp => {
+                let speed = if key.modifiers.contains(KeyModifiers::SHIFT) { 10 } else { 1 };
+                navigation::page_up(pane, speed);
+            }
+            KeyCode::PageDown => {
+                let speed = if key.modifiers.contains(KeyModifiers::SHIFT) { 10 } else { 1 };
+                navigation::page_down(pane, speed);
+            }
+            KeyCode::Home => navigation::move_home(pane),
+            KeyCode::End => navigation::move_end(pane),
+            _ => return Ok(false),
+        }
+        if matches!(
+            key.code,
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+                | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Home | KeyCode::End
+        ) {
+            // Moved away by hand, so next/previous start from the cursor again
+            self.current = None;
+        }
+        self.follow_active();
+        Ok(false)
+    }
+
+    /// Recompute the differences using the current comparison mode.
+    fn compare(&mut self) {
+        let [left, right] = &self.panes;
+        self.hunks = if self.resync {
+            resync_hunks(&left.bytes, &right.bytes)
+        } else {
+            strict_hunks(&left.bytes, &right.bytes)
+        };
+        self.current = None;
+        self.follow_active();
+    }
+
+    /// Move the inactive pane to the bytes lining up with the active pane's cursor, on the same screen row.
+    pub fn follow_active(&mut self) {
+        let active = &self.panes[self.active];
+        let pos = self.map_position(active.cursor_pos, self.active);
+        let row_offset = active.cursor_pos - active.offset;
+        let other = &mut self.panes[1 - self.active];
+        other.cursor_pos = pos.min(other.view_len() - 1);
+        other.offset = other.cursor_pos.saturating_sub(row_offset);
+    }
+
+    /// True if the byte at `pos` in pane `pane` is part of a difference.
+    pub fn differs(&self, pos: usize, pane: usize) -> bool {
+        let index = self.hunks.partition_point(|h| h.range(pane).end <= pos);
+        self.hunks.get(index).is_some_and(|h| h.range(pane).contains(&pos))
+    }
+
+    /// Position in the other pane's file that lines up with `pos` in pane `pane`.
+    fn map_position(&self, pos: usize, pane: usize) -> usize {
+        let index = self.hunks.partition_point(|h| h.range(pane).start <= pos);
+        let Some(hunk) = index.checked_sub(1).map(|i| &self.hunks[i]) else {
+            // Before the first difference both files are identical
+            return pos;
+        };
+        let (from, to) = (hunk.range(pane), hunk.range(1 - pane));
+        if from.is_empty() && pos == from.start {
+            // Sitting where the other file has extra bytes: show the start of them
+            to.start
+        } else if pos < from.end {
+            to.start + (pos - from.start).min(to.len().saturating_sub(1))
+        } else {
+            pos - from.end + to.end
+        }
+    }
+
+    fn next_difference(&mut self) {
+        let next = match self.current {
+            Some(index) => index + 1,
+            None => {
+                let cursor = self.panes[self.active].cursor_pos;
+                self.hunks.partition_point(|h| h.range(self.active).start <= cursor)
+            }
+        };
+        if next < self.hunks.len() {
+            self.goto_hunk(next);
+        }
+    }
+
+    fn prev_difference(&mut self) {
+        let prev = match self.current {
+            Some(index) => index.checked_sub(1),
+            None => {
+                let cursor = self.panes[self.active].cursor_pos;
+                self.hunks.partition_point(|h| h.range(self.active).start < cursor).checked_sub(1)
+            }
+        };
+        if let Some(index) = prev {
+            self.goto_hunk(index);
+        }
+    }
+
+    fn goto_hunk(&mut self, index: usize) {
+        let pane = &mut self.panes[self.active];
+        pane.cursor_pos = self.hunks[index].range(self.active).start.min(pane.view_len() - 1);
+        navigation::scroll_to_cursor(pane);
+        self.current = Some(index);
+        self.follow_active();
+    }
+
+    /// One line per difference for the summary popup.
+    fn summary(&self) -> Vec<String> {
+        let describe = |range: &Range<usize>| {
+            if range.is_empty() {
+                format!("{:<34}", format!("nothing at 0x{:08X}", range.start))
+            } else {
+                format!("{:<34}", format!("0x{:08X}-0x{:08X} ({} bytes)", range.start, range.end - 1, range.len()))
+            }
+        };
+        self.hunks
+            .iter()
+            .enumerate()
+            .map(|(i, hunk)| format!("#{:<5} {} | {}", i + 1, describe(&hunk.left), describe(&hunk.right)))
+            .collect()
+    }
+
+    /// Status line text: the two files, how much differs and which comparison is in use.
+    pub fn status(&self) -> String {
+        let name = |pane: &MicroHex| {
+            Path::new(&pane.filename)
+                .file_name()
+                .map_or_else(|| pane.filename.clone(), |n| n.to_string_lossy().into_owned())
+        };
+        let differing: usize = self.hunks.iter().map(|h| h.left.len().max(h.right.len())).sum();
+        let position = match self.current {
+            Some(index) => format!("difference {}/{}", index + 1, self.hunks.len()),
+            None => format!("{} difference(s)", self.hunks.len()),
+        };
+        format!(
+            "Diff: {} vs {} | {} | {} bytes differ | {}",
+            name(&self.panes[0]),
+            name(&self.panes[1]),
+            position,
+            differing,
+            if self.resync { "RESYNC" } else { "BY OFFSET" }
+        )
+    }
+}
+
+/// Differences found by comparing byte `i` of one file with byte `i` of the other. Bytes past the end of
+/// the shorter file all count as one final difference.
+pub fn strict_hunks(a: &PieceTable, b: &PieceTable) -> Vec<Hunk> {
+    let common = a.len().min(b.len());
+    let mut hunks = Vec::new();
+    let mut start = None;
+    let mut pos = 0;
+    while pos < common {
+        let end = (pos + CHUNK_SIZE).min(common);
+        let (x, y) = (a.read_range(pos..end), b.read_range(pos..end));
+        if x == y && start.is_none() {
+            pos = end;
+            continue;
+        }
+        for (i, (p, q)) in x.iter().zip(&y).enumerate() {
+            match (p != q, start) {
+                (true, None) => start = Some(pos + i),
+                (false, Some(s)) => {
+                    hunks.push(Hunk { left: s..pos + i, right: s..pos + i });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        pos = end;
+    }
+    if start.is_some() || a.len() != b.len() {
+        let s = start.unwrap_or(common);
+        hunks.push(Hunk { left: s..a.len(), right: s..b.len() });
+    }
+    hunks
+}
+
+/// Differences found by walking both files together and, at each mismatch, skipping ahead in one or both
+/// until they line up again. Greedy, so it favours the smallest skip rather than the smallest overall diff.
+pub fn resync_hunks(a: &PieceTable, b: &PieceTable) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        let same = common_prefix(a, i, b, j);
+        i += same;
+        j += same;
+        if i >= a.len() || j >= b.len() {
+            if i < a.len() || j < b.len() {
+                push_hunk(&mut hunks, i..a.len(), j..b.len());
+            }
+            return hunks;
+        }
+        let (skip_a, skip_b) = find_resync(a, i, b, j);
+        push_hunk(&mut hunks, i..i + skip_a, j..j + skip_b);
+        i += skip_a;
+        j += skip_b;
+    }
+}
+
+/// Add a difference, merging it into the previous one if nothing equal separates them.
+fn push_hunk(hunks: &mut Vec<Hunk>, left: Range<usize>, right: Range<usize>) {
+    if let Some(last) = hunks.last_mut()
+        && last.left.end == left.start
+        && last.right.end == right.start
+    {
+        last.left.end = left.end;
+        last.right.end = right.end;
+    } else {
+        hunks.push(Hunk { left, right });
+    }
+}
+
+/// Number of equal bytes starting at `i` in `a` and `j` in `b`.
+fn common_prefix(a: &PieceTable, i: usize, b: &PieceTable, j: usize) -> usize {
+    let mut same = 0;
+    loop {
+        let x = a.read_range(i + same..i + same + CHUNK_SIZE);
+        let y = b.read_range(j + same..j + same + CHUNK_SIZE);
+        let run = x.iter().zip(&y).take_while(|(p, q)| p == q).count();
+        same += run;
+        if run < CHUNK_SIZE {
+            return same;
+        }
+    }
+}
+
+/// How many bytes to skip in each file, starting from a mismatch at `i` and `j`, to reach the nearest point
+/// where `RESYNC_RUN` bytes match again. Without one nearby, a whole window on each side counts as changed.
+fn find_resync(a: &PieceTable, i: usize, b: &PieceTable, j: usize) -> (usize, usize) {
+    let window_a = a.read_range(i..i + RESYNC_WINDOW + RESYNC_RUN);
+    let window_b = b.read_range(j..j + RESYNC_WINDOW + RESYNC_RUN);
+
+    // Where each run of bytes starts in the right-hand window
+    let mut starts: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (skip_b, run) in window_b.windows(RESYNC_RUN).enumerate() {
+        starts.entry(run).or_default().push(skip_b);
+    }
+
+    // Smallest total skip wins; between equal totals prefer a plain substitution over a shift
+    let mut best: Option<(usize, usize)> = None;
+    let cost = |(x, y): (usize, usize)| (x + y, x.abs_diff(y));
+    for (skip_a, run) in window_a.windows(RESYNC_RUN).enumerate() {
+        if best.is_some_and(|(x, y)| skip_a > x + y) {
+            break;
+        }
+        for &skip_b in starts.get(run).into_iter().flatten() {
+            if best.is_none_or(|found| cost((skip_a, skip_b)) < cost(found)) {
+                best = Some((skip_a, skip_b));
+            }
+        }
+    }
+
+    best.unwrap_or_else(|| {
+        let rest_a = a.len() - i;
+        let rest_b = b.len() - j;
+        if rest_a <= RESYNC_WINDOW && rest_b <= RESYNC_WINDOW {
+            // Close to the end of both files and they never line up again
+            (rest_a, rest_b)
+        } else {
+            (rest_a.min(RESYNC_WINDOW), rest_b.min(RESYNC_WINDOW))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(hunks: Vec<Hunk>) -> Vec<(Range<usize>, Range<usize>)> {
+        hunks.into_iter().map(|hunk| (hunk.left, hunk.right)).collect()
+    }
+
+    fn strict(a: &[u8], b: &[u8]) -> Vec<(Range<usize>, Range<usize>)> {
+        ranges(strict_hunks(&PieceTable::from_bytes(a), &PieceTable::from_bytes(b)))
+    }
+
+    fn resync(a: &[u8], b: &[u8]) -> Vec<(Range<usize>, Range<usize>)> {
+        ranges(resync_hunks(&PieceTable::from_bytes(a), &PieceTable::from_bytes(b)))
+    }
+
+    /// 100 distinct bytes, so nothing lines up by accident.
+    fn sample() -> Vec<u8> {
+        (0..100).collect()
+    }
+
+    #[test]
+    fn strict_compares_offset_by_offset() {
+        assert!(strict(b"abcdefgh", b"abcdefgh").is_empty());
+        assert_eq!(strict(b"abcdefgh", b"abXdeYZh"), [(2..3, 2..3), (5..7, 5..7)]);
+        assert_eq!(strict(b"abcd", b"abcdef"), [(4..4, 4..6)]);
+        assert_eq!(strict(b"abcx", b"abcyzz"), [(3..4, 3..6)]);
+        assert_eq!(strict(b"", b"ab"), [(0..0, 0..2)]);
+    }
+
+    #[test]
+    fn strict_joins_differences_across_chunks() {
+        let a = vec![0; CHUNK_SIZE * 3];
+        let mut b = a.clone();
+        b[CHUNK_SIZE - 1] = 1;
+        b[CHUNK_SIZE] = 1;
+        b[CHUNK_SIZE * 2 + 5] = 1;
+        let (first, second) = (CHUNK_SIZE - 1..CHUNK_SIZE + 1, CHUNK_SIZE * 2 + 5..CHUNK_SIZE * 2 + 6);
+        assert_eq!(strict(&a, &b), [(first.clone(), first), (second.clone(), second)]);
+    }
+
+    #[test]
+    fn resync_finds_insertions_and_deletions() {
+        let a = sample();
+        let mut b = a.clone();
+        b.splice(50..50, [200, 201, 202]);
+        assert_eq!(resync(&a, &b), [(50..50, 50..53)]);
+        assert_eq!(resync(&b, &a), [(50..53, 50..50)]);
+        // Offset by offset, everything after the insertion differs
+        assert_eq!(strict(&a, &b), [(50..100, 50..103)]);
+    }
+
+    #[test]
+    fn resync_keeps_substitutions_in_place() {
+        let a = sample();
+        let mut b = a.clone();
+        b.splice(10..12, [250, 251]);
+        b.splice(70..71, [252, 253, 254]);
+        assert!(resync(&a, &a).is_empty());
+        assert_eq!(resync(&a, &b), [(10..12, 10..12), (70..71, 70..73)]);
+    }
+
+    #[test]
+    fn resync_reports_a_tail_that_never_lines_up() {
+        let a = sample();
+        let mut b = a[..90].to_vec();
+        b.extend([255; 5]);
+        assert_eq!(resync(&a, &b), [(90..100, 90..95)]);
+        assert_eq!(resync(&a, &a[..60]), [(60..100, 60..60)]);
+    }
+}
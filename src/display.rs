@@ -7,23 +7,122 @@ use std::io::{self, Write};
 use crossterm::{
     queue,
     terminal::{self, ClearType},
-    style::{Color, SetForegroundColor, SetBackgroundColor, ResetColor},
+    style::{Attribute, Color, SetAttribute, SetForegroundColor, SetBackgroundColor, ResetColor},
     cursor,
     event::{self, Event},
 };
 
 use crate::config::ColorConfig;
+use crate::diff::DiffView;
 use crate::editor::{MicroHex, EditMode};
+use crate::{inspector, navigation};
+use crate::encoding::Glyph;
+use crate::config::{EditorConfig, Endian, Radix};
 
-pub fn draw(editor: &mut MicroHex, colors: &ColorConfig) -> io::Result<()> {
+/// Rows used by everything except the data lines: status line, blank line and column header above them,
+/// help bar and prompt line below.
+const CHROME_ROWS: usize = 5;
+
+/// Columns taken by the inspector panel, including the gap between it and the data pane.
+const INSPECTOR_WIDTH: usize = 48;
+
+/// Width of the field name column in the inspector panel.
+const INSPECTOR_NAME_WIDTH: usize = 10;
+
+/// Number of data lines that fit in a terminal `rows` high.
+pub fn lines_per_page(rows: u16) -> usize {
+    (rows as usize).saturating_sub(CHROME_ROWS).max(1)
+}
+
+/// Width in columns of one line of the data pane.
+pub fn line_width(bytes_per_line: usize, settings: &EditorConfig) -> usize {
+    // Offset (10) + one cell per byte + 1 after each word + 1 between groups + ASCII margin (1) + 1 per ASCII byte
+    let words = bytes_per_line.div_ceil(word_size(settings));
+    let group_gaps = (0..bytes_per_line).filter(|&column| starts_group(column, settings)).count();
+    10 + bytes_per_line * settings.radix.digits() + words + group_gaps + 1 + bytes_per_line
+}
+
+/// Most bytes per line that fit in `width` columns: always whole words, and rounded down to whole groups
+/// if at least one fits.
+pub fn fit_bytes_per_line(width: usize, settings: &EditorConfig) -> usize {
+    let word_size = word_size(settings);
+    let mut bytes_per_line = word_size;
+    while line_width(bytes_per_line + word_size, settings) <= width {
+        bytes_per_line += word_size;
+    }
+    let group = group_bytes(settings);
+    if bytes_per_line >= group {
+        bytes_per_line -= bytes_per_line % group;
+    }
+    bytes_per_line
+}
+
+/// Narrowest data pane `settings` allow: one word per line, the same step `fit_bytes_per_line` works in.
+fn min_line_width(settings: &EditorConfig) -> usize {
+    line_width(word_size(settings), settings)
+}
+
+/// Bytes in each word of the data pane. A word size of 0 in the config is treated as single bytes, and so
+/// is any word size in octal or decimal, whose digits don't join up into the value of a word.
+fn word_size(settings: &EditorConfig) -> usize {
+    match settings.radix {
+        Radix::Hex | Radix::Binary => settings.word_size.max(1),
+        Radix::Octal | Radix::Decimal => 1,
+    }
+}
+
+/// Bytes from one group gap to the next: the group size, stretched to a whole number of words.
+fn group_bytes(settings: &EditorConfig) -> usize {
+    let word_size = word_size(settings);
+    if settings.group_size == 0 {
+        return word_size;
+    }
+    (1..=settings.group_size).map(|n| n * word_size).find(|n| n.is_multiple_of(settings.group_size)).unwrap()
+}
+
+/// True if column `column` of the data pane starts a new group and needs an extra space before it.
+/// Gaps only ever fall between words.
+fn starts_group(column: usize, settings: &EditorConfig) -> bool {
+    column > 0 && settings.group_size > 0 && column.is_multiple_of(group_bytes(settings))
+}
+
+/// Which byte of the line is shown in hex column `column`. Little-endian words are shown back to front.
+fn byte_in_column(column: usize, settings: &EditorConfig) -> usize {
+    let word_size = word_size(settings);
+    match settings.endian {
+        Endian::Big => column,
+        Endian::Little => column - column % word_size + (word_size - 1 - column % word_size),
+    }
+}
+
+/// Size the view to the terminal: lines per page from its height and bytes per line from `width`, either as
+/// many as fit in auto mode or the configured number if they fit. Wide cells such as binary can make a fixed
+/// line too long, in which case it's cut down to what fits. The offset is realigned to whole lines and the
+/// cursor kept in view.
+fn apply_layout(editor: &mut MicroHex, width: usize, rows: u16) {
+    editor.lines_per_page = lines_per_page(rows);
+    let bytes_per_line = match editor.settings.bytes_per_line {
+        0 => fit_bytes_per_line(width, &editor.settings),
+        n => n.next_multiple_of(word_size(&editor.settings)).min(fit_bytes_per_line(width, &editor.settings)),
+    };
+    if bytes_per_line != editor.bytes_per_line {
+        editor.bytes_per_line = bytes_per_line;
+        editor.offset -= editor.offset % bytes_per_line;
+    }
+    navigation::scroll_to_cursor(editor);
+}
+
+/// Draw the whole screen for `editor`. `buffer_list` names the other open buffers, if there are any.
+pub fn draw(editor: &mut MicroHex, buffer_list: &str, colors: &ColorConfig) -> io::Result<()> {
     let mut stdout = io::stdout();
     let (cols, rows) = terminal::size()?;
 
     // Calculate minimum size: 
-    // - Status bar (1) + blank (1) + header (1) + at least 4 lines of data (4) + help bar (1) = 8 rows minimum
-    // - For columns: offset (10) + 16*3 (hex bytes + spaces) + 2 (ASCII margin) + 16 (ASCII) = 76 columns minimum for 16 bytes/line
+    // - Status bar (1) + blank (1) + header (1) + at least 3 lines of data (3) + help bar (1) + prompt (1) = 8 rows minimum
+    // - For columns: whatever one word of the data pane needs, since lines are cut down to fit
     let min_lines = 8;
-    let min_cols = 76;
+    let inspector_width = if editor.show_inspector { INSPECTOR_WIDTH } else { 0 };
+    let min_cols = (min_line_width(&editor.settings) + inspector_width) as u16;
 
     if cols < min_cols || rows < min_lines {
         queue!(stdout, cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
@@ -32,21 +131,27 @@ pub fn draw(editor: &mut MicroHex, colors: &ColorConfig) -> io::Result<()> {
         return Ok(());
     }
 
-    // Dynamically recalculate lines_per_page for current term size
-    editor.lines_per_page = (rows as usize).saturating_sub(4).max(1);
+    // Dynamically recalculate the layout for current term size
+    apply_layout(editor, cols as usize - inspector_width, rows);
 
     queue!(stdout, cursor::MoveTo(0, 0))?;
-    draw_status_line(&mut stdout, editor, cols, colors)?;
+    draw_status_line(&mut stdout, editor, buffer_list, cols, colors)?;
     writeln!(stdout)?; // Blank line after status bar
-    draw_header(&mut stdout, editor.bytes_per_line, cols, colors)?;
+    draw_header(&mut stdout, editor, cols, colors)?;
 
-    let end_offset = (editor.offset + editor.bytes_per_line * editor.lines_per_page).min(editor.bytes.len());
+    let end_offset = (editor.offset + editor.bytes_per_line * editor.lines_per_page).min(editor.view_len());
 
+    // Highlight bytes that differ from what was loaded from disk
+    let is_changed = |pos: usize, byte: u8| pos < editor.bytes.len() && editor.bytes.original_byte(pos) != Some(byte);
     for line_start in (editor.offset..end_offset).step_by(editor.bytes_per_line) {
-        draw_line(&mut stdout, editor, line_start, colors)?;
+        draw_line(&mut stdout, editor, line_start, &is_changed, colors)?;
     }
     
     queue!(stdout, terminal::Clear(ClearType::FromCursorDown))?;
+    if editor.show_inspector {
+        let column = line_width(editor.bytes_per_line, &editor.settings) + 2;
+        draw_inspector(&mut stdout, editor, column, colors)?;
+    }
     draw_help_bar(&mut stdout, editor, cols, colors)?;
     stdout.flush()?;
     Ok(())
@@ -54,22 +159,41 @@ pub fn draw(editor: &mut MicroHex, colors: &ColorConfig) -> io::Result<()> {
 
 // This function is generic over any writer that implements std::io::Write (such as Stdout, a file, or a buffer).
 // Using W: Write allows us to reuse this function for testing, alternate outputs, or redirection if needed.
-fn draw_status_line<W: Write>(stdout: &mut W, editor: &MicroHex, cols: u16, colors: &ColorConfig) -> io::Result<()> {
+fn draw_status_line<W: Write>(
+    stdout: &mut W,
+    editor: &MicroHex,
+    buffer_list: &str,
+    cols: u16,
+    colors: &ColorConfig,
+) -> io::Result<()> {
     let file_size = editor.bytes.len();
-    let percent = if file_size > 0 {
-        ((editor.cursor_pos + 1) as f64 / file_size as f64) * 100.0
-    } else {
-        0.0
-    };
+    let percent = ((editor.cursor_pos + 1) as f64 / editor.view_len() as f64) * 100.0;
+    let typing = if editor.insert_mode { "INS" } else { "OVR" };
     let mode_str = match editor.mode {
+        EditMode::View if editor.selection_anchor.is_some() => "VISUAL",
         EditMode::View => "VIEW",
-        EditMode::EditHex => "EDIT HEX",
-        EditMode::EditAscii => "EDIT ASCII",
+        EditMode::EditHex => &format!("EDIT {} {}", editor.settings.radix.label(), typing),
+        EditMode::EditAscii => &format!("EDIT {} {}", editor.settings.encoding.label(), typing),
     };
-    let status = format!(
+    let mut status = format!(
         "File: {} ({} bytes) | {:.1}% | {} | Cursor: 0x{:X} ({})",
         editor.filename, file_size, percent, mode_str, editor.cursor_pos, editor.cursor_pos
     );
+    if let Some(range) = editor.selection() {
+        status.push_str(&format!(
+            " | Selection: 0x{:X}-0x{:X} ({} bytes)",
+            range.start,
+            range.end - 1,
+            range.len()
+        ));
+    }
+    if word_size(&editor.settings) > 1 {
+        let endian = if editor.settings.endian == Endian::Little { "LE" } else { "BE" };
+        status.push_str(&format!(" | {}-byte words {}", editor.settings.word_size, endian));
+    }
+    if !buffer_list.is_empty() {
+        status.push_str(&format!(" | Buffers: {buffer_list}"));
+    }
     let mut line = status.chars().take(cols as usize).collect::<String>();
     if line.len() < cols as usize {
         line.push_str(&" ".repeat(cols as usize - line.len()));
@@ -85,7 +209,7 @@ fn draw_status_line<W: Write>(stdout: &mut W, editor: &MicroHex, cols: u16, colo
 }
 
 fn draw_help_bar<W: Write>(stdout: &mut W, editor: &MicroHex, cols: u16, colors: &ColorConfig) -> io::Result<()> {
-    let help_row = (editor.lines_per_page + 2) as u16;
+    let help_row = (editor.lines_per_page + 3) as u16;
     
     // If we have an active search, show search info instead of normal help
     let help_text = if let Some(ref search_state) = editor.search_state {
@@ -95,16 +219,21 @@ fn draw_help_bar<W: Write>(stdout: &mut W, editor: &MicroHex, cols: u16, colors:
             search_state.total_matches()
         )
     } else {
-        String::from("^G Help   ^X Exit   ^S Save   ^E/Tab Mode   ^Z Undo   Home/End: Start/EOF   Arrows: Move   Del: Delete, Back: Null")
+        String::from("^G Help   M-Q Quit   ^S Save   ^O Save As   ^E/Tab Mode   ^Z Undo   ^Y Redo   ^C Copy   ^X Cut   ^V Paste   Home/End: Start/EOF   Arrows: Move   Del: Delete, Back: Null")
     };
     
-    let mut line = help_text.chars().take(cols as usize).collect::<String>();
+    draw_bar(stdout, help_row, &help_text, cols, colors)
+}
+
+/// Draw `text` as a full-width bar in the help bar colours on row `row`.
+fn draw_bar<W: Write>(stdout: &mut W, row: u16, text: &str, cols: u16, colors: &ColorConfig) -> io::Result<()> {
+    let mut line = text.chars().take(cols as usize).collect::<String>();
     if line.len() < cols as usize {
         line.push_str(&" ".repeat(cols as usize - line.len()));
     }
     queue!(
         stdout,
-        cursor::MoveTo(0, help_row),
+        cursor::MoveTo(0, row),
         SetBackgroundColor(Color::AnsiValue(colors.help_bg)),
         SetForegroundColor(Color::AnsiValue(colors.help_fg)),
     )?;
@@ -113,14 +242,17 @@ fn draw_help_bar<W: Write>(stdout: &mut W, editor: &MicroHex, cols: u16, colors:
     Ok(())
 }
 
-fn draw_header<W: Write>(stdout: &mut W, bytes_per_line: usize, cols: u16, colors: &ColorConfig) -> io::Result<()> {
+fn draw_header<W: Write>(stdout: &mut W, editor: &MicroHex, cols: u16, colors: &ColorConfig) -> io::Result<()> {
     queue!(stdout, SetForegroundColor(Color::AnsiValue(colors.header_fg)))?; // Configurable header color
+    // Each word is labelled with the offset of its first byte
+    let word_size = word_size(&editor.settings);
     let mut header = String::from("Offset    ");
-    for i in 0..bytes_per_line {
-        if i == 8 { header.push(' '); }
-        header.push_str(&format!("{:02x} ", i));
+    for i in (0..editor.bytes_per_line).step_by(word_size) {
+        if starts_group(i, &editor.settings) { header.push(' '); }
+        header.push_str(&format!("{:<width$} ", format!("{:02x}", i), width = word_size * editor.settings.radix.digits()));
     }
-    header.push_str(" ASCII");
+    header.push(' ');
+    header.push_str(editor.settings.encoding.label());
     let mut line = header.chars().take(cols as usize).collect::<String>();
     if line.len() < cols as usize {
         line.push_str(&" ".repeat(cols as usize - line.len()));
@@ -130,34 +262,74 @@ fn draw_header<W: Write>(stdout: &mut W, bytes_per_line: usize, cols: u16, color
     Ok(())
 }
 
-fn draw_line<W: Write>(stdout: &mut W, editor: &MicroHex, line_start: usize, colors: &ColorConfig) -> io::Result<()> {
+/// Draw one line of hex and ASCII starting at `line_start`. Bytes for which `is_changed` returns true are
+/// shown in the changed colour.
+pub fn draw_line<W: Write>(
+    stdout: &mut W,
+    editor: &MicroHex,
+    line_start: usize,
+    is_changed: &dyn Fn(usize, u8) -> bool,
+    colors: &ColorConfig,
+) -> io::Result<()> {
     write!(stdout, "{:08x}: ", line_start)?;
 
-    let line_end = (line_start + editor.bytes_per_line).min(editor.bytes.len());
-    let chunk = &editor.bytes[line_start..line_end];
+    let line_end = (line_start + editor.bytes_per_line).min(editor.view_len());
+    let mut chunk = editor.bytes.read_range(line_start..line_end);
+    let real_len = chunk.len(); // Anything after this is virtual bytes past the end of the file
+    chunk.resize(line_end - line_start, 0);
 
-    // Hex bytes
-    for (j, byte) in chunk.iter().enumerate() {
-        if j == 8 { write!(stdout, " ")?; }
+    // Cells in the active radix, a word at a time. Columns whose byte is past the end of the view are left blank.
+    let word_size = word_size(&editor.settings);
+    let radix = editor.settings.radix;
+    for column in 0..editor.bytes_per_line {
+        if column > 0 && column.is_multiple_of(word_size) { write!(stdout, " ")?; }
+        if starts_group(column, &editor.settings) { write!(stdout, " ")?; }
+        let j = byte_in_column(column, &editor.settings);
+        let Some(byte) = chunk.get(j) else {
+            write!(stdout, "{}", " ".repeat(radix.digits()))?;
+            continue;
+        };
         let pos = line_start + j;
-        set_cell_color(stdout, editor, pos, *byte, EditMode::EditHex, colors)?;
-        write!(stdout, "{:02x}", byte)?;
+        set_cell_color(stdout, editor, pos, *byte, is_changed(pos, *byte), EditMode::EditHex, colors)?;
+        let cell = if j < real_len { radix.format(*byte) } else { "_".repeat(radix.digits()) };
+        if pos == editor.cursor_pos && editor.mode == EditMode::EditHex {
+            // Underline the digit the next key press will replace
+            let (typed, rest) = cell.split_at(editor.pending_digits as usize % radix.digits());
+            let (next, rest) = rest.split_at(1);
+            write!(stdout, "{typed}")?;
+            queue!(stdout, SetAttribute(Attribute::Underlined))?;
+            write!(stdout, "{next}")?;
+            queue!(stdout, SetAttribute(Attribute::NoUnderline))?;
+            write!(stdout, "{rest}")?;
+        } else {
+            write!(stdout, "{cell}")?;
+        }
         queue!(stdout, ResetColor)?;
-        write!(stdout, " ")?;
-    }
-
-    // Padding
-    for p in chunk.len()..editor.bytes_per_line {
-        if p == 8 { write!(stdout, " ")?; }
-        write!(stdout, "   ")?;
     }
-    write!(stdout, " ")?;
+    write!(stdout, "  ")?;
 
-    // ASCII
+    // Text, decoded with a few bytes either side so characters crossing the line's edges come out right
+    let encoding = editor.settings.encoding;
+    let window_start = line_start.saturating_sub(encoding.context());
+    let window_end = (line_start + real_len + encoding.context()).min(editor.bytes.len()).max(window_start);
+    let window = editor.bytes.read_range(window_start..window_end);
+    let glyphs = encoding.glyphs(&window, window_start, line_start - window_start, real_len);
+    let mut covered = false; // Drawn over by the double-width character before it
     for (j, byte) in chunk.iter().enumerate() {
+        if std::mem::take(&mut covered) {
+            continue;
+        }
         let pos = line_start + j;
-        let c = if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' };
-        set_cell_color(stdout, editor, pos, *byte, EditMode::EditAscii, colors)?;
+        let c = match glyphs.get(j) {
+            None | Some(Glyph::Continuation) => ' ', // Virtual bytes past the end have no glyph
+            Some(Glyph::Char(c)) => *c,
+            Some(Glyph::Wide(c)) => {
+                covered = true;
+                *c
+            }
+            Some(Glyph::Invalid) => '.',
+        };
+        set_cell_color(stdout, editor, pos, *byte, is_changed(pos, *byte), EditMode::EditAscii, colors)?;
         write!(stdout, "{}", c)?;
         queue!(stdout, ResetColor)?;
     }
@@ -166,15 +338,52 @@ fn draw_line<W: Write>(stdout: &mut W, editor: &MicroHex, line_start: usize, col
     Ok(())
 }
 
+/// Draw the inspector panel from column `column`: every field decoded from the bytes at the cursor, scrolled so
+/// the selected field is visible.
+fn draw_inspector<W: Write>(stdout: &mut W, editor: &MicroHex, column: usize, colors: &ColorConfig) -> io::Result<()> {
+    let width = INSPECTOR_WIDTH - 2;
+    let fields = inspector::fields();
+    let bytes = editor.inspector_bytes();
+
+    queue!(stdout, cursor::MoveTo(column as u16, 2), SetForegroundColor(Color::AnsiValue(colors.header_fg)))?;
+    let title = format!("Value at 0x{:X}   Alt+Up/Down, Enter: edit", editor.cursor_pos);
+    write!(stdout, "{}", pad_to_width(&title, width))?;
+    queue!(stdout, ResetColor)?;
+
+    let first = editor.inspector_row.saturating_sub(editor.lines_per_page - 1);
+    for row in 0..editor.lines_per_page {
+        queue!(stdout, cursor::MoveTo(column as u16, (3 + row) as u16))?;
+        let index = first + row;
+        let Some(field) = fields.get(index) else {
+            write!(stdout, "{}", " ".repeat(width))?;
+            continue;
+        };
+        let value = field.decode(&bytes).unwrap_or_default();
+        let text = pad_to_width(&format!("{:<INSPECTOR_NAME_WIDTH$}{value}", field.name()), width);
+        if index == editor.inspector_row {
+            queue!(
+                stdout,
+                SetBackgroundColor(Color::AnsiValue(colors.cursor_inactive_bg)),
+                SetForegroundColor(Color::AnsiValue(colors.cursor_inactive_fg))
+            )?;
+            write!(stdout, "{text}")?;
+            queue!(stdout, ResetColor)?;
+        } else {
+            write!(stdout, "{text}")?;
+        }
+    }
+    Ok(())
+}
+
 fn set_cell_color<W: Write>(
     stdout: &mut W,
     editor: &MicroHex,
     pos: usize,
     byte: u8,
+    is_changed: bool,
     active_mode: EditMode,
     colors: &ColorConfig,
 ) -> io::Result<()> {
-    let is_changed = editor.original_bytes.get(pos) != Some(&byte);
     if pos == editor.cursor_pos {
         match &editor.mode {
             m if *m == active_mode => {
@@ -202,11 +411,17 @@ fn set_cell_color<W: Write>(
                 )?
             }
         }
-    } else if is_changed {
+        return Ok(());
+    }
+
+    if editor.selection().is_some_and(|range| range.contains(&pos)) {
+        queue!(stdout, SetBackgroundColor(Color::AnsiValue(colors.selection_bg)))?; // Selected byte
+    }
+    if is_changed {
         queue!(stdout, SetForegroundColor(Color::AnsiValue(colors.changed_fg)))?; // Changed byte
     } else if byte == 0 {
         queue!(stdout, SetForegroundColor(Color::AnsiValue(colors.null_fg)))?; // Null byte
-    } else if byte < 0x20 || byte >= 0x7f {
+    } else if !(0x20..0x7f).contains(&byte) {
         queue!(stdout, SetForegroundColor(Color::AnsiValue(colors.control_fg)))?; // Control/non-printable
     } else if byte.is_ascii_graphic() || byte == b' ' {
         queue!(stdout, SetForegroundColor(Color::AnsiValue(colors.printable_fg)))?; // Printable
@@ -214,6 +429,138 @@ fn set_cell_color<W: Write>(
     Ok(())
 }
 
+/// Draw the two panes of a diff side by side, with differing bytes highlighted. Each pane gets half the
+/// terminal, laid out just like the normal view.
+pub fn draw_diff(view: &mut DiffView, colors: &ColorConfig) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let (cols, rows) = terminal::size()?;
+
+    let min_lines = 8;
+    let min_cols = (2 * min_line_width(&view.panes[0].settings) + 2) as u16;
+    if cols < min_cols || rows < min_lines {
+        queue!(stdout, cursor::MoveTo(0, 0), terminal::Clear(ClearType::All))?;
+        writeln!(stdout, "Terminal too small! Resize to at least {min_cols}x{min_lines}.")?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    let pane_width = (cols as usize - 2) / 2;
+    // Lay out the pane being navigated; the other one takes the same layout and follows it
+    apply_layout(&mut view.panes[view.active], pane_width, rows);
+    let bytes_per_line = view.panes[view.active].bytes_per_line;
+    let lines_per_page = view.panes[view.active].lines_per_page;
+    let passive = &mut view.panes[1 - view.active];
+    passive.bytes_per_line = bytes_per_line;
+    passive.lines_per_page = lines_per_page;
+    view.follow_active();
+    let width = line_width(bytes_per_line, &view.panes[0].settings);
+
+    queue!(stdout, cursor::MoveTo(0, 0))?;
+    let status = pad_to_width(&view.status(), cols as usize);
+    queue!(
+        stdout,
+        SetBackgroundColor(Color::AnsiValue(colors.status_bg)),
+        SetForegroundColor(Color::AnsiValue(colors.status_fg))
+    )?;
+    write!(stdout, "{status}")?;
+    queue!(stdout, ResetColor)?;
+
+    // File names above each pane, with the one being navigated marked
+    let right_col = (width + 2) as u16;
+    queue!(
+        stdout,
+        cursor::MoveTo(0, 1),
+        terminal::Clear(ClearType::CurrentLine),
+        cursor::MoveTo(0, 2),
+        terminal::Clear(ClearType::CurrentLine)
+    )?;
+    for (i, pane) in view.panes.iter().enumerate() {
+        let column = if i == 0 { 0 } else { right_col };
+        let marker = if i == view.active { '>' } else { ' ' };
+        queue!(stdout, cursor::MoveTo(column, 1))?;
+        write!(stdout, "{}", pad_to_width(&format!("{marker} {} ({} bytes)", pane.filename, pane.bytes.len()), width))?;
+        queue!(stdout, cursor::MoveTo(column, 2))?;
+        draw_header(&mut stdout, pane, width as u16, colors)?;
+    }
+
+    for row in 0..lines_per_page {
+        let screen_row = (3 + row) as u16;
+        queue!(stdout, cursor::MoveTo(0, screen_row), terminal::Clear(ClearType::CurrentLine))?;
+        for (i, pane) in view.panes.iter().enumerate() {
+            let line_start = pane.offset + row * bytes_per_line;
+            if line_start >= pane.view_len() {
+                continue;
+            }
+            let is_changed = |pos: usize, _: u8| view.differs(pos, i);
+            queue!(stdout, cursor::MoveTo(if i == 0 { 0 } else { right_col }, screen_row))?;
+            draw_line(&mut stdout, pane, line_start, &is_changed, colors)?;
+        }
+    }
+
+    let help = "Tab: Switch pane   n/N: Next/prev difference   d: List differences   r: Resync on/off   q: Quit";
+    draw_bar(&mut stdout, (lines_per_page + 3) as u16, help, cols, colors)?;
+    stdout.flush()
+}
+
+/// Draw a selectable list over the data area, scrolled so the selected entry is visible.
+/// The footer replaces the help bar while the list is open.
+pub fn draw_list_popup(
+    editor: &MicroHex,
+    title: &str,
+    items: &[String],
+    selected: usize,
+    footer: &str,
+    colors: &ColorConfig,
+) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let (cols, _) = terminal::size()?;
+    let width = cols as usize;
+    let visible = editor.lines_per_page;
+    let first = selected.saturating_sub(visible - 1);
+
+    queue!(stdout, cursor::MoveTo(0, 2), SetForegroundColor(Color::AnsiValue(colors.header_fg)))?;
+    write!(stdout, "{}", pad_to_width(&format!("{title} ({} entries)", items.len()), width))?;
+    queue!(stdout, ResetColor)?;
+
+    for row in 0..visible {
+        queue!(stdout, cursor::MoveTo(0, (3 + row) as u16))?;
+        let index = first + row;
+        match items.get(index) {
+            Some(item) if index == selected => {
+                queue!(
+                    stdout,
+                    SetBackgroundColor(Color::AnsiValue(colors.cursor_active_bg)),
+                    SetForegroundColor(Color::AnsiValue(colors.cursor_active_fg))
+                )?;
+                write!(stdout, "{}", pad_to_width(item, width))?;
+                queue!(stdout, ResetColor)?;
+            }
+            Some(item) => write!(stdout, "{}", pad_to_width(item, width))?,
+            None => queue!(stdout, terminal::Clear(ClearType::CurrentLine))?,
+        }
+    }
+
+    queue!(
+        stdout,
+        cursor::MoveTo(0, (editor.lines_per_page + 3) as u16),
+        SetBackgroundColor(Color::AnsiValue(colors.help_bg)),
+        SetForegroundColor(Color::AnsiValue(colors.help_fg)),
+    )?;
+    write!(stdout, "{}", pad_to_width(footer, width))?;
+    queue!(stdout, ResetColor)?;
+    stdout.flush()
+}
+
+/// Truncate or space-pad `text` to exactly `width` characters.
+fn pad_to_width(text: &str, width: usize) -> String {
+    let mut line = text.chars().take(width).collect::<String>();
+    let len = line.chars().count();
+    if len < width {
+        line.push_str(&" ".repeat(width - len));
+    }
+    line
+}
+
 /// Show a message at the bottom of the screen and wait for any key press.
 pub fn show_message(editor: &MicroHex, message: &str, colors: &ColorConfig) -> io::Result<()> {
     let mut stdout = io::stdout();
@@ -1,9 +1,19 @@
 //! Editing logic for hex and ASCII modes.
 //!
-//! Handles byte/nibble entry, ASCII editing, undo stack, deletion, and mode cycling.
+//! Handles digit-by-digit byte entry in the active radix, ASCII editing (overwriting or inserting), undo stack,
+//! deletion, and mode cycling.
 //! All editing actions are performed on the `MicroHex` state and support undo/redo.
+//! Every change to the buffer goes through `push_undo`, which records only the bytes that changed
+//! as a new state in the branching `UndoTree`.
 
-use crate::editor::{MicroHex, EditMode, UndoState};
+use std::ops::Range;
+
+use crate::buffer::{Change, PieceTable};
+use crate::clipboard::{self, Format};
+use crate::config::Radix;
+use crate::editor::{MicroHex, EditMode, UndoState, CursorState};
+use crate::history::{StateId, ROOT};
+use crate::navigation;
 
 pub fn cycle_mode(editor: &mut MicroHex) {
     editor.mode = match editor.mode {
@@ -11,107 +21,732 @@ pub fn cycle_mode(editor: &mut MicroHex) {
         EditMode::EditHex => EditMode::EditAscii,
         EditMode::EditAscii => EditMode::View,
     };
-    editor.pending_nibble = None; // Clear any pending nibble when switching modes
+    editor.pending_digits = 0; // Start the next cell from its first digit when switching modes
+    if editor.mode == EditMode::View {
+        // Virtual bytes are only there to edit into, so drop them when going back to viewing
+        editor.virtual_len = 0;
+        editor.cursor_pos = editor.cursor_pos.min(editor.view_len() - 1);
+        navigation::scroll_to_cursor(editor);
+    }
 }
 
 pub fn undo(editor: &mut MicroHex) {
-    if let Some(prev) = editor.undo_stack.pop() {
-        editor.bytes = prev.bytes;
-        editor.cursor_pos = prev.cursor_pos;
-        editor.offset = prev.offset;
-        editor.pending_nibble = prev.pending_nibble;
-        editor.modified = editor.bytes != editor.original_bytes;
+    if let Some(change) = editor.history.undo() {
+        revert(&mut editor.bytes, change);
+        let before = change.before;
+        before.restore(editor);
+        update_modified(editor);
+    }
+}
+
+pub fn redo(editor: &mut MicroHex) {
+    if let Some(change) = editor.history.redo() {
+        reapply(&mut editor.bytes, change);
+        let after = change.after;
+        after.restore(editor);
+        update_modified(editor);
+    }
+}
+
+/// Move to the state recorded just before the current one in time, even if it's on another branch (like vim's `g-`).
+pub fn older(editor: &mut MicroHex) {
+    if editor.history.current() > ROOT {
+        goto_state(editor, editor.history.current() - 1);
+    }
+}
+
+/// Move to the state recorded just after the current one in time (like vim's `g+`).
+pub fn newer(editor: &mut MicroHex) {
+    if editor.history.current() < editor.history.latest() {
+        goto_state(editor, editor.history.current() + 1);
+    }
+}
+
+/// Jump to any state in the history by undoing back to where the branches meet and redoing down to `target`.
+pub fn goto_state(editor: &mut MicroHex, target: StateId) {
+    if target == editor.history.current() {
+        return;
+    }
+    let (undo_path, redo_path) = editor.history.path(editor.history.current(), target);
+    for _ in undo_path {
+        if let Some(change) = editor.history.undo() {
+            revert(&mut editor.bytes, change);
+        }
+    }
+    for id in redo_path {
+        let change = editor.history.redo_into(id);
+        reapply(&mut editor.bytes, change);
+    }
+    // Land where the target change left the cursor
+    let cursor = if target == ROOT {
+        editor.history.node(1).change.before
+    } else {
+        editor.history.node(target).change.after
+    };
+    cursor.restore(editor);
+    update_modified(editor);
+}
+
+/// The changes that turn the file on disk into the current buffer, in the order they have to be applied.
+pub fn pending_changes(editor: &MicroHex) -> Vec<UndoState> {
+    let (undo_path, redo_path) = editor.history.path(editor.history.saved(), editor.history.current());
+    let reverted = undo_path.into_iter().map(|id| editor.history.node(id).change.inverted());
+    let reapplied = redo_path.into_iter().map(|id| editor.history.node(id).change.clone());
+    reverted.chain(reapplied).collect()
+}
+
+/// Apply a change that didn't come from the keyboard (e.g. recovered from a swap file) as a new undoable edit.
+pub fn replay(editor: &mut MicroHex, change: UndoState) {
+    reapply(&mut editor.bytes, &change);
+    change.after.restore(editor);
+    editor.history.push(change);
+    update_modified(editor);
+}
+
+/// One line per state for the history popup, oldest first. Line `i` describes state `i`.
+pub fn history_entries(editor: &MicroHex) -> Vec<String> {
+    let marker = |id: StateId| {
+        let current = if id == editor.history.current() { '>' } else { ' ' };
+        let saved = if editor.history.saved() == id { '*' } else { ' ' };
+        format!("{current}{saved}")
+    };
+    let mut entries = vec![format!("{} #0     original file", marker(ROOT))];
+    for id in 1..=editor.history.latest() {
+        let node = editor.history.node(id);
+        let change = &node.change;
+        let kind = if change.old_bytes.is_empty() {
+            "insert"
+        } else if change.new_bytes.is_empty() {
+            "delete"
+        } else {
+            "overwrite"
+        };
+        let mut line = format!(
+            "{} #{:<5} {:<9} at 0x{:08X}  -{} +{} bytes",
+            marker(id), id, kind, change.pos, change.old_bytes.len(), change.new_bytes.len()
+        );
+        // A parent that isn't the previous state means this change started a new branch
+        if node.parent != id - 1 {
+            line.push_str(&format!("  (branch from #{})", node.parent));
+        }
+        entries.push(line);
     }
+    entries
+}
+
+/// One line per entry of `PieceTable::changes` for the changes panel, with a preview of the old and new bytes.
+pub fn change_entries(editor: &MicroHex, changes: &[Change]) -> Vec<String> {
+    const PREVIEW_LEN: usize = 8;
+    let preview = |bytes: &[u8], total: usize| {
+        let mut text = bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+        if total > bytes.len() {
+            text.push_str(" ...");
+        }
+        text
+    };
+    changes
+        .iter()
+        .map(|change| {
+            let (old_len, new_len) = (change.original.len(), change.current.len());
+            let kind = if old_len == 0 {
+                "insert"
+            } else if new_len == 0 {
+                "delete"
+            } else {
+                "modify"
+            };
+            let old = editor.bytes.original_range(change.original.start..change.original.start + old_len.min(PREVIEW_LEN));
+            let new = editor.bytes.read_range(change.current.start..change.current.start + new_len.min(PREVIEW_LEN));
+            format!(
+                "{:<6} at 0x{:08X}  -{} +{} bytes  [{}] -> [{}]",
+                kind, change.current.start, old_len, new_len, preview(&old, old_len), preview(&new, new_len)
+            )
+        })
+        .collect()
+}
+
+/// Overwrite the bytes at the cursor with `data` as a single undoable edit, leaving the cursor where it is.
+pub fn overwrite_at_cursor(editor: &mut MicroHex, data: &[u8]) {
+    let before = CursorState::of(editor);
+    let edit = overwrite(editor, editor.cursor_pos, data);
+    editor.pending_digits = 0;
+    push_undo(editor, edit, before);
+}
+
+/// Put the original bytes back in place of one change, as a single undoable edit.
+pub fn revert_change(editor: &mut MicroHex, change: &Change) {
+    let before = CursorState::of(editor);
+    let pos = change.current.start;
+    let old_bytes = editor.bytes.read_range(change.current.clone());
+    let new_bytes = editor.bytes.original_range(change.original.clone());
+    editor.bytes.delete(pos, old_bytes.len());
+    editor.bytes.insert(pos, &new_bytes);
+    editor.pending_digits = 0;
+    editor.cursor_pos = pos.min(editor.view_len() - 1);
+    navigation::scroll_to_cursor(editor);
+    push_undo(editor, Edit { pos, old_bytes, new_bytes }, before);
+}
+
+fn revert(bytes: &mut PieceTable, change: &UndoState) {
+    bytes.delete(change.pos, change.new_bytes.len());
+    bytes.insert(change.pos, &change.old_bytes);
+}
+
+fn reapply(bytes: &mut PieceTable, change: &UndoState) {
+    bytes.delete(change.pos, change.old_bytes.len());
+    bytes.insert(change.pos, &change.new_bytes);
 }
 
-fn push_undo(editor: &mut MicroHex) {
-    editor.undo_stack.push(UndoState {
-        bytes: editor.bytes.clone(),
-        cursor_pos: editor.cursor_pos,
-        offset: editor.offset,
-        pending_nibble: editor.pending_nibble,
+/// Record a change that has just been applied to the buffer, as a new state on top of the current one.
+fn push_undo(editor: &mut MicroHex, edit: Edit, before: CursorState) {
+    editor.history.push(UndoState {
+        pos: edit.pos,
+        old_bytes: edit.old_bytes,
+        new_bytes: edit.new_bytes,
+        before,
+        after: CursorState::of(editor),
     });
+    update_modified(editor);
+}
+
+fn update_modified(editor: &mut MicroHex) {
+    editor.modified = editor.history.is_modified();
+}
+
+/// A change that has been applied to the buffer but not recorded yet.
+struct Edit {
+    pos: usize,
+    old_bytes: Vec<u8>,
+    new_bytes: Vec<u8>,
+}
+
+/// Overwrite bytes at `pos` with `data`. Writing into the virtual bytes past the end of the file makes
+/// them real, so any gap between the end of the file and `pos` is filled with zeros.
+fn overwrite(editor: &mut MicroHex, pos: usize, data: &[u8]) -> Edit {
+    let len = editor.bytes.len();
+    let (start, new_bytes) = if pos > len {
+        let mut padded = vec![0; pos - len];
+        padded.extend_from_slice(data);
+        (len, padded)
+    } else {
+        (pos, data.to_vec())
+    };
+    let old_bytes = editor.bytes.read_range(start..start + new_bytes.len());
+    editor.bytes.replace(start, &new_bytes);
+    editor.virtual_len = editor.virtual_len.saturating_sub(editor.bytes.len() - len);
+    Edit { pos: start, old_bytes, new_bytes }
+}
+
+/// Advance the cursor after a completed byte, growing the view with a virtual byte if we've walked off the end.
+fn advance_cursor(editor: &mut MicroHex) {
+    editor.cursor_pos += 1;
+    if editor.cursor_pos >= editor.view_len() {
+        editor.virtual_len += 1;
+    }
+    // Scroll window if cursor goes below visible window
+    if editor.cursor_pos >= editor.offset + (editor.bytes_per_line * editor.lines_per_page) {
+        editor.offset += editor.bytes_per_line;
+    }
 }
 
 pub fn edit_byte(editor: &mut MicroHex, c: char) {
-    push_undo(editor); // Saves copy of current file data onto undo stack before making any changes
+    let before = CursorState::of(editor);
     match editor.mode {
         EditMode::EditAscii => {
-            // ASCII editing mode
-            if c.is_ascii() {
-                editor.bytes[editor.cursor_pos] = c as u8;
-                editor.modified = true;
-                // Always auto-advance after entering a character
-                editor.cursor_pos += 1;
-                // If we're now at the end in edit mode, append a new null byte
-                if editor.cursor_pos >= editor.bytes.len() {
-                    editor.bytes.push(0);
-                    // Don't modify original_bytes - it should stay as the original file content
-                }
-                // Scroll window if cursor goes below visible window
-                if editor.cursor_pos >= editor.offset + (editor.bytes_per_line * editor.lines_per_page) {
-                    editor.offset += editor.bytes_per_line;
+            // Text editing mode: the character is written in the text pane's encoding, taking as many
+            // bytes as it needs, and the cursor moves past all of them
+            if let Some(bytes) = editor.settings.encoding.encode(c) {
+                let edit = insert_or_overwrite(editor, editor.cursor_pos, &bytes, editor.insert_mode, Vec::new());
+                for _ in 0..bytes.len() {
+                    advance_cursor(editor);
                 }
+                push_undo(editor, edit, before);
             }
         }
         EditMode::EditHex => {
-            // Only accept hex digits (0-9, a-f, A-F)
-            if let Some(d) = c.to_digit(16) {
-                if editor.pending_nibble.is_none() {
-                    // First nibble: set high nibble, keep low nibble
-                    editor.bytes[editor.cursor_pos] = (editor.bytes[editor.cursor_pos] & 0x0F) | ((d as u8) << 4);
-                    editor.pending_nibble = Some(d as u8);
-                    editor.modified = true;
-                } else {
-                    // Second nibble: set low nibble, keep high nibble
-                    editor.bytes[editor.cursor_pos] = (editor.bytes[editor.cursor_pos] & 0xF0) | (d as u8);
-                    editor.pending_nibble = None;
-                    editor.modified = true;
-                    // Advance cursor after completing the byte
-                    editor.cursor_pos += 1;
-                    // If we're now at the end in edit mode, append a new null byte
-                    if editor.cursor_pos >= editor.bytes.len() {
-                        editor.bytes.push(0);
-                        // Don't modify original_bytes - it should stay as the original file content
-                    }
-                    // Scroll window if needed
-                    if editor.cursor_pos >= editor.offset + (editor.bytes_per_line * editor.lines_per_page) {
-                        editor.offset += editor.bytes_per_line;
-                    }
-                }
+            // Digits in the active radix replace the cell's digits from left to right, and the cursor moves on
+            // once the last one is typed. In binary, Space flips the bit instead of setting it.
+            // In octal and decimal not every cell is a byte, so the first digit clears the ones after it
+            // (typing 2, 5, 5 gives 200, 250, 255) and a digit that would go past 255 is ignored.
+            // In insert mode the first digit inserts a new zero byte to type into instead.
+            let radix = editor.settings.radix;
+            let index = match editor.pending_digits as usize {
+                i if i < radix.digits() => i,
+                _ => 0,
+            };
+            let inserting = editor.insert_mode && index == 0;
+            let current = if inserting { 0 } else { editor.byte_at(editor.cursor_pos) };
+            let mut cell: Vec<char> = radix.format(current).chars().collect();
+            cell[index] = match c {
+                ' ' if radix == Radix::Binary => if cell[index] == '0' { '1' } else { '0' },
+                c if c.is_digit(radix.base()) => c.to_ascii_lowercase(),
+                _ => return,
+            };
+            if index == 0 && matches!(radix, Radix::Octal | Radix::Decimal) {
+                cell[1..].fill('0');
             }
+            let Ok(byte) = u8::from_str_radix(&cell.iter().collect::<String>(), radix.base()) else {
+                return;
+            };
+            let edit = insert_or_overwrite(editor, editor.cursor_pos, &[byte], inserting, Vec::new());
+            if index + 1 < radix.digits() {
+                editor.pending_digits = index as u8 + 1;
+            } else {
+                editor.pending_digits = 0;
+                advance_cursor(editor);
+            }
+            push_undo(editor, edit, before);
         }
         EditMode::View => {}
     }
 }
 
 pub fn backspace(editor: &mut MicroHex) {
-    push_undo(editor);
     // Set the current byte to null (0x00), then move the cursor back one (if not at 0)
-    if editor.cursor_pos < editor.bytes.len() {
-        editor.bytes[editor.cursor_pos] = 0;
-        editor.modified = true;
-        if editor.cursor_pos > 0 {
-            editor.cursor_pos -= 1;
-        }
+    // Virtual bytes past the end are already null, so there the cursor just moves back
+    let before = CursorState::of(editor);
+    let edit = (editor.cursor_pos < editor.bytes.len()).then(|| overwrite(editor, editor.cursor_pos, &[0]));
+    if editor.cursor_pos > 0 {
+        editor.cursor_pos -= 1;
+    }
+    if let Some(edit) = edit {
+        push_undo(editor, edit, before);
+    }
+}
+
+/// Backspace in insert mode: remove the byte before the cursor, like in a text editor.
+pub fn delete_before_cursor(editor: &mut MicroHex) {
+    if editor.cursor_pos == 0 {
+        return;
+    }
+    let before = CursorState::of(editor);
+    editor.cursor_pos -= 1;
+    editor.pending_digits = 0;
+    let pos = editor.cursor_pos;
+    let edit = if pos < editor.bytes.len() {
+        let old_bytes = editor.bytes.read_range(pos..pos + 1);
+        editor.bytes.delete(pos, 1);
+        Some(Edit { pos, old_bytes, new_bytes: Vec::new() })
+    } else {
+        // A virtual byte just shrinks the view
+        editor.virtual_len -= 1;
+        None
+    };
+    navigation::scroll_to_cursor(editor);
+    if let Some(edit) = edit {
+        push_undo(editor, edit, before);
     }
 }
 
 pub fn delete_prev_byte(editor: &mut MicroHex) {
-    push_undo(editor);
     // Completely remove the byte at the current cursor position
     // But never delete the last remaining byte
-    if editor.cursor_pos < editor.bytes.len() && editor.bytes.len() > 1 {
-        editor.bytes.remove(editor.cursor_pos);
-        // Don't modify original_bytes - it should preserve the original file content for change tracking
-        editor.modified = true;
-        // Move cursor back after deletion (unless we're at position 0)
-        if editor.cursor_pos > 0 {
-            editor.cursor_pos -= 1;
+    if editor.view_len() <= 1 {
+        return;
+    }
+    let before = CursorState::of(editor);
+    let edit = if editor.cursor_pos < editor.bytes.len() {
+        let pos = editor.cursor_pos;
+        let old_bytes = editor.bytes.read_range(pos..pos + 1);
+        editor.bytes.delete(pos, 1);
+        Some(Edit { pos, old_bytes, new_bytes: Vec::new() })
+    } else {
+        // Deleting a virtual byte just shrinks the view; there's nothing to undo
+        editor.virtual_len -= 1;
+        None
+    };
+    // Move cursor back after deletion (unless we're at position 0)
+    if editor.cursor_pos > 0 {
+        editor.cursor_pos -= 1;
+    }
+    // Adjust offset if needed
+    if editor.cursor_pos < editor.offset {
+        editor.offset = editor.offset.saturating_sub(editor.bytes_per_line);
+    }
+    if let Some(edit) = edit {
+        push_undo(editor, edit, before);
+    }
+}
+
+/// Delete the bytes in `range` as one undoable edit, leaving the cursor where the range started and ending
+/// visual mode. Any virtual bytes in the range are simply dropped.
+pub fn delete_range(editor: &mut MicroHex, range: Range<usize>) {
+    let before = CursorState::of(editor);
+    let end = range.end.min(editor.bytes.len());
+    let start = range.start.min(end);
+    let old_bytes = editor.bytes.read_range(start..end);
+    editor.bytes.delete(start, old_bytes.len());
+    editor.virtual_len = editor.virtual_len.saturating_sub(range.end - end);
+    editor.selection_anchor = None;
+    editor.pending_digits = 0;
+    editor.cursor_pos = range.start.min(editor.view_len() - 1);
+    navigation::scroll_to_cursor(editor);
+    if !old_bytes.is_empty() {
+        push_undo(editor, Edit { pos: start, old_bytes, new_bytes: Vec::new() }, before);
+    }
+}
+
+/// Paste `data` as one undoable edit, leaving the cursor just after it. With a selection, the selected bytes
+/// are replaced; otherwise `data` is inserted before the cursor or written over the bytes from the cursor on.
+pub fn paste(editor: &mut MicroHex, data: &[u8], insert: bool) {
+    if data.is_empty() {
+        return;
+    }
+    let before = CursorState::of(editor);
+    let edit = match editor.selection() {
+        Some(range) => {
+            let end = range.end.min(editor.bytes.len());
+            let start = range.start.min(end);
+            let old_bytes = editor.bytes.read_range(start..end);
+            editor.bytes.delete(start, old_bytes.len());
+            editor.virtual_len = editor.virtual_len.saturating_sub(range.end - end);
+            editor.cursor_pos = range.start;
+            insert_or_overwrite(editor, range.start, data, true, old_bytes)
         }
-        // Adjust offset if needed
-        if editor.cursor_pos < editor.offset {
-            editor.offset = editor.offset.saturating_sub(editor.bytes_per_line);
+        None => insert_or_overwrite(editor, editor.cursor_pos, data, insert, Vec::new()),
+    };
+    editor.selection_anchor = None;
+    editor.pending_digits = 0;
+    for _ in 0..data.len() {
+        advance_cursor(editor);
+    }
+    navigation::scroll_to_cursor(editor);
+    push_undo(editor, edit, before);
+}
+
+/// Put `data` at `pos`, inserting it or overwriting what's there. `removed` holds bytes just deleted from `pos`
+/// as part of the same edit. Past the end of the file there's nothing to insert before, so virtual bytes are
+/// always overwritten (and nothing real can have been removed there).
+fn insert_or_overwrite(editor: &mut MicroHex, pos: usize, data: &[u8], insert: bool, removed: Vec<u8>) -> Edit {
+    if !insert || pos > editor.bytes.len() {
+        return overwrite(editor, pos, data);
+    }
+    editor.bytes.insert(pos, data);
+    Edit { pos, old_bytes: removed, new_bytes: data.to_vec() }
+}
+
+/// What `fill` writes into a range.
+#[derive(Debug, PartialEq)]
+pub enum Fill {
+    Pattern(Vec<u8>),                // Repeated from the start of the range; one byte fills it with that value
+    Counter { start: u8, step: u8 }, // start, start + step, ... wrapping around after 0xff
+    Random(u64),                     // Seed of a pseudo-random sequence, so the same seed gives the same bytes
+}
+
+impl Fill {
+    /// Read what's typed at the fill prompt: `count:<start>[,<step>]`, `random:<seed>`, a single byte value
+    /// (a number like `0`, `255` or `0xff`), or a pattern written as a hex string, C array or Python bytes
+    /// literal, or with a clipboard prefix such as `text:`.
+    pub fn parse(text: &str) -> Result<Fill, String> {
+        let number = |value: &str| {
+            let value = value.trim();
+            parse_number(value).ok_or_else(|| format!("'{value}' is not a number"))
+        };
+        let byte = |value: &str| match number(value)? {
+            n @ 0..=0xff => Ok(n as u8),
+            n => Err(format!("{n} doesn't fit in a byte")),
+        };
+        if let Some(counter) = text.strip_prefix("count:") {
+            let (start, step) = counter.split_once(',').unwrap_or((counter, "1"));
+            return Ok(Fill::Counter { start: byte(start)?, step: byte(step)? });
+        }
+        if let Some(seed) = text.strip_prefix("random:") {
+            return Ok(Fill::Random(number(seed)?));
+        }
+        // A bare number is always one byte, never hex pairs, so `10` means the same as `0xa` whatever its length.
+        // Anything with spaces in it is a list of hex pairs instead
+        if parse_number(text.trim()).is_some() {
+            return Ok(Fill::Pattern(vec![byte(text)?]));
+        }
+        let pattern = match clipboard::parse_text(text)? {
+            (bytes, Format::Hex | Format::CArray | Format::Python) => bytes,
+            (bytes, _) if clipboard::has_format_prefix(text) => bytes,
+            _ => {
+                return Err(format!(
+                    "'{text}' is not a byte or a pattern like de ad be ef, {{0xde, 0xad}}, b'\\xde\\xad' or text:..."
+                ));
+            }
+        };
+        if pattern.is_empty() {
+            return Err(String::from("Nothing to fill with"));
+        }
+        Ok(Fill::Pattern(pattern))
+    }
+
+    /// The first `len` bytes of the fill.
+    fn generate(&self, len: usize) -> Vec<u8> {
+        match self {
+            Fill::Pattern(pattern) => pattern.iter().copied().cycle().take(len).collect(),
+            Fill::Counter { start, step } => {
+                (0..len).map(|i| start.wrapping_add(step.wrapping_mul(i as u8))).collect()
+            }
+            Fill::Random(seed) => {
+                // SplitMix64: tiny, fast and good enough for filler, which is all this is for
+                let mut state = *seed;
+                let mut next = || {
+                    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    let mut z = state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                    (z ^ (z >> 31)).to_le_bytes()
+                };
+                std::iter::repeat_with(&mut next).flatten().take(len).collect()
+            }
+        }
+    }
+}
+
+/// A single decimal number, or hex, octal or binary with a `0x`, `0o` or `0b` prefix. Unlike go-to offsets
+/// there's no arithmetic, sign or spaces, so a typo can't quietly turn into a different number.
+fn parse_number(text: &str) -> Option<u64> {
+    let lower = text.to_ascii_lowercase();
+    let (radix, digits) = match lower.get(..2) {
+        Some("0x") => (16, &lower[2..]),
+        Some("0o") => (8, &lower[2..]),
+        Some("0b") => (2, &lower[2..]),
+        _ => (10, &lower[..]),
+    };
+    // from_str_radix takes a sign of its own
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u64::from_str_radix(digits, radix).ok()
+}
+
+/// Fill `range` as one undoable edit, either writing over it (growing the file if it reaches past the end) or
+/// inserting that many filled bytes at its start. The cursor is left at the start of the range.
+pub fn fill(editor: &mut MicroHex, range: Range<usize>, fill: &Fill, insert: bool) {
+    if range.is_empty() {
+        return;
+    }
+    let before = CursorState::of(editor);
+    let data = fill.generate(range.len());
+    let edit = insert_or_overwrite(editor, range.start, &data, insert, Vec::new());
+    editor.selection_anchor = None;
+    editor.pending_digits = 0;
+    editor.cursor_pos = range.start;
+    navigation::scroll_to_cursor(editor);
+    push_undo(editor, edit, before);
+}
+
+/// Number of null bytes at the very end of the file (not counting virtual bytes).
+pub fn trailing_null_count(editor: &MicroHex) -> usize {
+    const CHUNK: usize = 64 * 1024;
+    let mut end = editor.bytes.len();
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK);
+        let chunk = editor.bytes.read_range(start..end);
+        match chunk.iter().rposition(|&b| b != 0) {
+            Some(i) => return editor.bytes.len() - (start + i + 1),
+            None => end = start,
         }
     }
-}
\ No newline at end of file
+    editor.bytes.len()
+}
+
+/// Truncate the file's trailing null bytes (padding) as a single undoable change.
+pub fn trim_trailing_nulls(editor: &mut MicroHex) {
+    let padding = trailing_null_count(editor);
+    if padding == 0 {
+        return;
+    }
+    let before = CursorState::of(editor);
+    let new_len = editor.bytes.len() - padding;
+    let old_bytes = editor.bytes.read_range(new_len..editor.bytes.len());
+    editor.bytes.delete(new_len, padding);
+    editor.virtual_len = 0;
+    editor.cursor_pos = editor.cursor_pos.min(editor.view_len() - 1);
+    navigation::scroll_to_cursor(editor);
+    push_undo(editor, Edit { pos: new_len, old_bytes, new_bytes: Vec::new() }, before);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editing(data: &[u8], mode: EditMode) -> MicroHex {
+        let mut editor = MicroHex::for_test("", data);
+        editor.mode = mode;
+        editor
+    }
+
+    fn contents(editor: &MicroHex) -> Vec<u8> {
+        editor.bytes.read_range(0..editor.bytes.len())
+    }
+
+    fn typed(editor: &mut MicroHex, keys: &str) {
+        for c in keys.chars() {
+            edit_byte(editor, c);
+        }
+    }
+
+    fn undo_all(editor: &mut MicroHex) {
+        while editor.history.current() != ROOT {
+            undo(editor);
+        }
+    }
+
+    #[test]
+    fn hex_digits_fill_the_cell_before_moving_on() {
+        let mut editor = editing(b"abc", EditMode::EditHex);
+        typed(&mut editor, "4");
+        assert_eq!((contents(&editor), editor.pending_digits, editor.cursor_pos), (b"Abc".to_vec(), 1, 0));
+        typed(&mut editor, "F");
+        assert_eq!((contents(&editor), editor.pending_digits, editor.cursor_pos), (b"Obc".to_vec(), 0, 1));
+        typed(&mut editor, "g");
+        assert_eq!((contents(&editor), editor.pending_digits), (b"Obc".to_vec(), 0));
+    }
+
+    #[test]
+    fn decimal_and_octal_digits_clear_the_rest_of_the_cell() {
+        let mut editor = editing(&[0x61, 0x61], EditMode::EditHex);
+        editor.settings.radix = Radix::Decimal;
+        typed(&mut editor, "2");
+        assert_eq!(contents(&editor), [200, 0x61]);
+        typed(&mut editor, "55");
+        assert_eq!((contents(&editor), editor.cursor_pos), (vec![255, 0x61], 1));
+        // 3xx doesn't fit in a byte
+        typed(&mut editor, "3");
+        assert_eq!((contents(&editor), editor.pending_digits), (vec![255, 0x61], 0));
+
+        let mut editor = editing(&[0], EditMode::EditHex);
+        editor.settings.radix = Radix::Octal;
+        typed(&mut editor, "4");
+        assert_eq!(contents(&editor), [0]);
+        typed(&mut editor, "377");
+        assert_eq!(contents(&editor), [0o377]);
+    }
+
+    #[test]
+    fn space_flips_bits_in_binary() {
+        let mut editor = editing(&[0, 0], EditMode::EditHex);
+        editor.settings.radix = Radix::Binary;
+        typed(&mut editor, " ");
+        assert_eq!((contents(&editor)[0], editor.pending_digits), (0b1000_0000, 1));
+        // Digits set bits and anything else is ignored
+        typed(&mut editor, "1 2 ");
+        assert_eq!((contents(&editor)[0], editor.pending_digits), (0b1111_0000, 4));
+        typed(&mut editor, "0001");
+        assert_eq!((contents(&editor)[0], editor.cursor_pos), (0b1111_0001, 1));
+        // Each digit is its own undo step
+        undo(&mut editor);
+        assert_eq!((contents(&editor)[0], editor.pending_digits, editor.cursor_pos), (0b1111_0000, 7, 0));
+    }
+
+    #[test]
+    fn insert_mode_typing_adds_bytes() {
+        let mut editor = editing(b"ab", EditMode::EditHex);
+        editor.insert_mode = true;
+        typed(&mut editor, "41");
+        assert_eq!((contents(&editor), editor.cursor_pos), (b"Aab".to_vec(), 1));
+        typed(&mut editor, "4");
+        assert_eq!(contents(&editor), b"A@ab");
+
+        let mut editor = editing(b"ab", EditMode::EditAscii);
+        editor.insert_mode = true;
+        typed(&mut editor, "xy");
+        assert_eq!((contents(&editor), editor.cursor_pos), (b"xyab".to_vec(), 2));
+        editor.insert_mode = false;
+        typed(&mut editor, "z");
+        assert_eq!(contents(&editor), b"xyzb");
+        undo_all(&mut editor);
+        assert_eq!(contents(&editor), b"ab");
+    }
+
+    #[test]
+    fn typing_past_the_end_makes_the_gap_real() {
+        let mut editor = editing(b"ab", EditMode::EditHex);
+        editor.virtual_len = 3;
+        editor.cursor_pos = 4;
+        typed(&mut editor, "ff");
+        assert_eq!(contents(&editor), [b'a', b'b', 0, 0, 0xff]);
+        // The cursor walks on into a new virtual byte
+        assert_eq!((editor.cursor_pos, editor.virtual_len), (5, 1));
+        undo_all(&mut editor);
+        assert_eq!(contents(&editor), b"ab");
+
+        // There's nothing to insert before out there, so insert mode writes too
+        let mut editor = editing(b"ab", EditMode::EditAscii);
+        editor.insert_mode = true;
+        editor.virtual_len = 2;
+        editor.cursor_pos = 3;
+        typed(&mut editor, "x");
+        assert_eq!(contents(&editor), b"ab\0x");
+    }
+
+    #[test]
+    fn paste_inserts_overwrites_or_replaces_the_selection() {
+        let mut editor = editing(b"abcd", EditMode::EditHex);
+        editor.cursor_pos = 1;
+        paste(&mut editor, b"XY", false);
+        assert_eq!((contents(&editor), editor.cursor_pos), (b"aXYd".to_vec(), 3));
+        paste(&mut editor, b"XY", true);
+        assert_eq!((contents(&editor), editor.cursor_pos), (b"aXYXYd".to_vec(), 5));
+        // Past the end it grows the file
+        paste(&mut editor, b"123", false);
+        assert_eq!(contents(&editor), b"aXYXY123");
+
+        let mut editor = editing(b"abcd", EditMode::EditHex);
+        editor.selection_anchor = Some(1);
+        editor.cursor_pos = 2;
+        paste(&mut editor, b"XYZ", false);
+        assert_eq!((contents(&editor), editor.cursor_pos, editor.selection_anchor), (b"aXYZd".to_vec(), 4, None));
+        undo(&mut editor);
+        assert_eq!(contents(&editor), b"abcd");
+    }
+
+    #[test]
+    fn fill_reads_bare_numbers_as_one_byte() {
+        assert_eq!(Fill::parse("0"), Ok(Fill::Pattern(vec![0])));
+        assert_eq!(Fill::parse("10"), Ok(Fill::Pattern(vec![10])));
+        assert_eq!(Fill::parse("100"), Ok(Fill::Pattern(vec![100])));
+        assert_eq!(Fill::parse("255"), Ok(Fill::Pattern(vec![255])));
+        assert_eq!(Fill::parse("0xff"), Ok(Fill::Pattern(vec![0xff])));
+        assert!(Fill::parse("1000").is_err());
+        assert!(Fill::parse("300").is_err());
+        assert!(Fill::parse("0x100").is_err());
+        assert_eq!(Fill::parse(" 0b101 "), Ok(Fill::Pattern(vec![5])));
+    }
+
+    #[test]
+    fn fill_never_reads_go_to_syntax() {
+        // Spaces separate hex pairs rather than being squeezed out of one number
+        assert_eq!(Fill::parse("00 01"), Ok(Fill::Pattern(vec![0, 1])));
+        assert_eq!(Fill::parse("01 02"), Ok(Fill::Pattern(vec![1, 2])));
+        assert_eq!(Fill::parse("10 20"), Ok(Fill::Pattern(vec![0x10, 0x20])));
+        for input in ["50%", "+5", "-1", "2*3", "(1)", "count:1+1", "random:-1"] {
+            assert!(Fill::parse(input).is_err(), "{input:?} should be an error");
+        }
+    }
+
+    #[test]
+    fn fill_patterns_need_an_explicit_form() {
+        assert_eq!(Fill::parse("ff"), Ok(Fill::Pattern(vec![0xff])));
+        assert_eq!(Fill::parse("de ad be ef"), Ok(Fill::Pattern(vec![0xde, 0xad, 0xbe, 0xef])));
+        assert_eq!(Fill::parse("{1, 2}"), Ok(Fill::Pattern(vec![1, 2])));
+        assert_eq!(Fill::parse(r"b'AB'"), Ok(Fill::Pattern(b"AB".to_vec())));
+        assert_eq!(Fill::parse("text:PAD"), Ok(Fill::Pattern(b"PAD".to_vec())));
+        assert!(Fill::parse("hello").is_err());
+        assert!(Fill::parse("text:").is_err());
+    }
+
+    #[test]
+    fn fill_reads_counters_and_seeds() {
+        assert_eq!(Fill::parse("count:0"), Ok(Fill::Counter { start: 0, step: 1 }));
+        assert_eq!(Fill::parse("count:0x10,2"), Ok(Fill::Counter { start: 0x10, step: 2 }));
+        assert!(Fill::parse("count:256").is_err());
+        assert_eq!(Fill::parse("random:1234"), Ok(Fill::Random(1234)));
+        assert!(Fill::parse("random:x").is_err());
+    }
+
+    #[test]
+    fn fill_generates_bytes() {
+        assert_eq!(Fill::Pattern(vec![1, 2, 3]).generate(7), [1, 2, 3, 1, 2, 3, 1]);
+        assert_eq!(Fill::Counter { start: 0xfe, step: 1 }.generate(4), [0xfe, 0xff, 0, 1]);
+        assert_eq!(Fill::Counter { start: 0, step: 0x80 }.generate(3), [0, 0x80, 0]);
+        let random = Fill::Random(42).generate(20);
+        assert_eq!(random.len(), 20);
+        assert_eq!(random, Fill::Random(42).generate(20));
+        assert_ne!(random, Fill::Random(43).generate(20));
+    }
+}
@@ -1,20 +1,31 @@
 //! Main editor state machine and event loop.
 //!
-//! Contains the `MicroHex` struct, which holds all editor state, and implements the main TUI loop (`run`).
+//! Contains the `MicroHex` struct, which holds all state for one open file.
 //! Handles file I/O, mode management, user prompts, and dispatches navigation/edit/display actions.
-//! All user input is processed here and routed to the appropriate module.
+//! Key presses are passed in by the `Workspace` event loop and routed to the appropriate module from here.
 
-use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::ops::Range;
 use crossterm::queue;
 use crossterm::{
-    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, ClearType},
+    terminal::{self, ClearType},
     cursor,
     event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
-    execute,
 };
 
-use crate::{display, navigation, edit, config::ColorConfig, search};
+use crate::{display, navigation, edit, search, journal, save, goto, inspector, clipboard};
+use crate::buffer::PieceTable;
+use crate::bookmarks::{Bookmarks, JumpList};
+use crate::config::{ColorConfig, EditorConfig, Endian, Radix};
+use crate::save::FileStamp;
+use crate::history::{StateId, UndoTree, ROOT};
+
+/// How often to check whether another program has changed the file.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+/// How many bytes a fill may add beyond the current file length, so a mistyped count can't exhaust memory.
+const MAX_FILL_GROWTH: usize = 256 * 1024 * 1024;
 
 #[derive(PartialEq)]
 pub enum EditMode {
@@ -23,110 +34,324 @@ pub enum EditMode {
     EditAscii,
 }
 
-#[derive(Clone)]
-pub struct UndoState {
-    pub bytes: Vec<u8>,
+/// What the user did in a list popup opened with `select_from_list`.
+pub enum ListAction {
+    Select(usize),    // Enter pressed on an entry
+    Key(char, usize), // One of the list's extra keys pressed on an entry
+}
+
+/// Cursor and view position, restored alongside an undone or redone change.
+#[derive(Clone, Copy)]
+pub struct CursorState {
     pub cursor_pos: usize,
     pub offset: usize,
-    pub pending_nibble: Option<u8>,
+    pub pending_digits: u8,
+}
+
+impl CursorState {
+    pub fn of(editor: &MicroHex) -> Self {
+        Self {
+            cursor_pos: editor.cursor_pos,
+            offset: editor.offset,
+            pending_digits: editor.pending_digits,
+        }
+    }
+
+    pub fn restore(&self, editor: &mut MicroHex) {
+        // A cursor that was out in the virtual bytes past the end gets them back
+        editor.virtual_len = editor.virtual_len.max((self.cursor_pos + 1).saturating_sub(editor.bytes.len()));
+        editor.cursor_pos = self.cursor_pos;
+        editor.offset = self.offset;
+        editor.pending_digits = self.pending_digits;
+        navigation::scroll_to_cursor(editor);
+    }
+}
+
+/// A single recorded edit: `old_bytes` at `pos` were replaced by `new_bytes`.
+/// An insert has no old bytes and a delete has no new bytes, so only what actually changed is stored.
+#[derive(Clone)]
+pub struct UndoState {
+    pub pos: usize,
+    pub old_bytes: Vec<u8>,
+    pub new_bytes: Vec<u8>,
+    pub before: CursorState,
+    pub after: CursorState,
+}
+
+impl UndoState {
+    /// The change that undoes this one.
+    pub fn inverted(&self) -> Self {
+        Self {
+            pos: self.pos,
+            old_bytes: self.new_bytes.clone(),
+            new_bytes: self.old_bytes.clone(),
+            before: self.after,
+            after: self.before,
+        }
+    }
 }
 
 pub struct MicroHex {
-    pub original_bytes: Vec<u8>,
-    pub bytes: Vec<u8>,
-    pub undo_stack: Vec<UndoState>,
+    pub bytes: PieceTable, // File contents; also remembers the original data for change highlighting
+    pub virtual_len: usize, // Zero bytes shown past the end of the file for the cursor to grow into; never saved
+    pub history: UndoTree,
     pub filename: String,
     pub offset: usize, // Current view offset (which byte we start displaying from)
     pub cursor_pos: usize, // Which byte the cursor is on
+    pub selection_anchor: Option<usize>, // Where the selection started, while in visual mode
     pub bytes_per_line: usize,
     pub lines_per_page: usize,
     pub mode: EditMode,
+    pub insert_mode: bool, // Typing inserts new bytes before the cursor instead of overwriting it
     pub modified: bool,
-    pub pending_nibble: Option<u8>, // Stores the first hex digit if one has been entered
+    pub pending_digits: u8, // Digits already typed into the cursor's cell; the next one replaces the digit after them
     pub search_state: Option<search::SearchState>, // Active search session, if any
+    pub bookmarks: Bookmarks, // Marks and named bookmarks
+    pub jumps: JumpList,
+    pub show_inspector: bool,  // Whether the data inspector panel is shown beside the data
+    pub inspector_row: usize,  // Selected field in the inspector
+    pub settings: EditorConfig,
+    swap_state: StateId,       // History state last written to the swap file
+    last_swap_write: Instant,
+    disk_stamp: Option<FileStamp>,    // The file as we last loaded or saved it
+    ignored_stamp: Option<FileStamp>, // An outside change the user chose not to reload
+    last_disk_check: Instant,
 }
 
 impl MicroHex {
-    pub fn new(filename: String, bytes: Vec<u8>) -> io::Result<Self> {
+    pub fn new(filename: String, bytes: PieceTable, settings: EditorConfig) -> io::Result<Self> {
         let (_, rows) = terminal::size()?;
-        // Subtract rows for: status line (1) + blank line (1) + header (1) + blank line (1) + bottom margin (1) = 5 rows
-        let lines_per_page = (rows as usize).saturating_sub(4).max(1);
+        Ok(Self::with_page_height(filename, bytes, settings, display::lines_per_page(rows)))
+    }
 
-        Ok(Self {
-            original_bytes: bytes.clone(),
+    /// An editor for `filename` without a terminal, showing the file in memory.
+    #[cfg(test)]
+    pub fn for_test(filename: &str, data: &[u8]) -> Self {
+        Self::with_page_height(filename.to_string(), PieceTable::from_bytes(data), EditorConfig::default(), 20)
+    }
+
+    fn with_page_height(filename: String, bytes: PieceTable, settings: EditorConfig, lines_per_page: usize) -> Self {
+        // In auto mode this is only a starting point; drawing fits it to the terminal
+        let bytes_per_line = if settings.bytes_per_line == 0 { 16 } else { settings.bytes_per_line };
+
+        // An empty file still needs one (virtual) byte for the cursor to sit on
+        let virtual_len = if bytes.is_empty() { 1 } else { 0 };
+        let disk_stamp = FileStamp::of(Path::new(&filename)).ok();
+
+        Self {
             bytes,
-            undo_stack: Vec::new(),
+            virtual_len,
+            history: UndoTree::new(),
             filename,
             offset: 0,
             cursor_pos: 0,
-            bytes_per_line: 16,
+            selection_anchor: None,
+            bytes_per_line,
             lines_per_page,
             mode: EditMode::View,
+            insert_mode: false,
             modified: false,
-            pending_nibble: None,
+            pending_digits: 0,
             search_state: None,
-        })
+            bookmarks: Bookmarks::default(),
+            jumps: JumpList::default(),
+            show_inspector: false,
+            inspector_row: 0,
+            settings,
+            swap_state: ROOT,
+            last_swap_write: Instant::now(),
+            disk_stamp,
+            ignored_stamp: None,
+            last_disk_check: Instant::now(),
+        }
     }
 
-    pub fn run(&mut self, colors: &ColorConfig) -> io::Result<()> {
-        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
-        terminal::enable_raw_mode()?;
-        execute!(io::stdout(), terminal::Clear(ClearType::All))?;
-
-        loop {
-            display::draw(self, colors)?;
+    /// Number of bytes shown: the file itself plus any virtual bytes past its end. Always at least 1.
+    pub fn view_len(&self) -> usize {
+        (self.bytes.len() + self.virtual_len).max(1)
+    }
 
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    if self.handle_key_event(key, colors)? {
-                        break;
-                    }
-                }
-            }
-        }
+    /// Selected bytes, from the anchor to the cursor (inclusive, in whichever order), while in visual mode.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?.min(self.view_len() - 1);
+        Some(anchor.min(self.cursor_pos)..anchor.max(self.cursor_pos) + 1)
+    }
 
-        terminal::disable_raw_mode()?;
-        execute!(io::stdout(), cursor::Show, LeaveAlternateScreen)?;
-        Ok(())
+    /// Byte at `pos` as displayed, reading virtual bytes past the end of the file as zero.
+    pub fn byte_at(&self, pos: usize) -> u8 {
+        self.bytes.get(pos).unwrap_or(0)
     }
 
-    fn handle_key_event(&mut self, key: crossterm::event::KeyEvent, colors: &ColorConfig) -> io::Result<bool> {
+    /// Handle a key press meant for this buffer. Quitting and switching buffers are handled by the `Workspace`,
+    /// which also owns the `clipboard` so bytes can be copied from one buffer and pasted into another.
+    pub fn handle_key_event(
+        &mut self,
+        key: crossterm::event::KeyEvent,
+        clipboard: &mut Vec<u8>,
+        colors: &ColorConfig,
+    ) -> io::Result<()> {
         match key.code {
 
             // FILE/MODE CONTROLS
-            KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::ALT) => {
-                if self.modified {
-                    if let Some(ans) = self.prompt("File modified. Save before exit? (y/n/c): ")? {
-                        match ans.to_lowercase().as_str() {
-                            "y" => { self.save()?; return Ok(true); }
-                            "n" => return Ok(true),
-                            _ => return Ok(false),
-                        }
-                    }
-                } else {
-                    return Ok(true);
-                }
-            }
             KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) && self.modified => {
-                if let Some(ans) = self.prompt("Really save changes? (y/n): ")? {
-                    if ans.to_lowercase() == "y" {
-                        self.save()?;
-                    }
+                if let Some(ans) = self.prompt("Really save changes? (y/n): ")?
+                    && ans.to_lowercase() == "y"
+                {
+                    self.save_reporting_errors(colors)?;
                 }
             }
+            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.save_as(colors)?;
+            }
             KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 edit::cycle_mode(self);
             }
             KeyCode::Tab => edit::cycle_mode(self),
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::ALT) => {
+                let padding = edit::trailing_null_count(self);
+                if padding == 0 {
+                    display::show_message(self, "No trailing null bytes to truncate. Press any key to continue...", colors)?;
+                } else if let Some(ans) = self.prompt(&format!("Truncate {padding} trailing null byte(s)? (y/n): "))?
+                    && ans.to_lowercase() == "y"
+                {
+                    edit::trim_trailing_nulls(self);
+                }
+            }
 
 
             // UNDO CONTROLS
+            KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                edit::redo(self);
+            }
+            KeyCode::Char('z' | 'Z')
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.modifiers.contains(KeyModifiers::SHIFT) =>
+            {
+                edit::redo(self);
+            }
             KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 edit::undo(self);
             }
+            KeyCode::Char('-') if key.modifiers.contains(KeyModifiers::ALT) => edit::older(self),
+            KeyCode::Char('=') if key.modifiers.contains(KeyModifiers::ALT) => edit::newer(self),
+            KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::ALT) => {
+                let entries = edit::history_entries(self);
+                let footer = "Up/Down: Select   Enter: Go to state   Esc: Close   (> current, * saved)";
+                if let Some(ListAction::Select(id)) =
+                    self.select_from_list("Undo history", &entries, self.history.current(), &[], footer, colors)?
+                {
+                    edit::goto_state(self, id);
+                }
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => self.show_changes(colors)?,
+
+
+            // DISPLAY CONTROLS
+            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::ALT) => {
+                // Single bytes, then 2, 4 and 8 byte words
+                self.settings.word_size = match self.settings.word_size {
+                    0 | 1 => 2,
+                    2 => 4,
+                    4 => 8,
+                    _ => 1,
+                };
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.settings.radix = match self.settings.radix {
+                    Radix::Hex => Radix::Binary,
+                    Radix::Binary => Radix::Octal,
+                    Radix::Octal => Radix::Decimal,
+                    Radix::Decimal => Radix::Hex,
+                };
+                self.pending_digits = 0;
+            }
+            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.settings.encoding = self.settings.encoding.next();
+            }
+            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.settings.endian = match self.settings.endian {
+                    Endian::Big => Endian::Little,
+                    Endian::Little => Endian::Big,
+                };
+            }
+
+
+            // DATA INSPECTOR
+            KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.show_inspector = !self.show_inspector;
+            }
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) && self.show_inspector => {
+                self.inspector_row = self.inspector_row.saturating_sub(1);
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) && self.show_inspector => {
+                self.inspector_row = (self.inspector_row + 1).min(inspector::fields().len() - 1);
+            }
+            KeyCode::Enter if self.show_inspector => self.edit_inspector_value(colors)?,
+
+
+            // SELECTION
+            KeyCode::Char('v')
+                if self.mode == EditMode::View && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.selection_anchor = match self.selection_anchor {
+                    Some(_) => None,
+                    None => Some(self.cursor_pos),
+                };
+            }
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End
+                if key.modifiers.contains(KeyModifiers::SHIFT) =>
+            {
+                // Shift+movement starts visual mode; after that the selection follows the cursor
+                self.selection_anchor.get_or_insert(self.cursor_pos);
+                match key.code {
+                    KeyCode::Up => navigation::move_up(self),
+                    KeyCode::Down => navigation::move_down(self),
+                    KeyCode::Left => navigation::move_left(self),
+                    KeyCode::Right => navigation::move_right(self),
+                    KeyCode::Home => navigation::move_home(self),
+                    _ => navigation::move_end(self),
+                }
+            }
+            KeyCode::Esc if self.selection_anchor.is_some() => self.selection_anchor = None,
+            KeyCode::Delete if self.mode != EditMode::View && self.selection_anchor.is_some() => {
+                if let Some(range) = self.selection() {
+                    edit::delete_range(self, range);
+                }
+            }
+
+
+            // CLIPBOARD
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                if let Some(range) = self.selection() {
+                    self.copy(range, clipboard, colors)?;
+                    self.selection_anchor = None;
+                }
+            }
+            KeyCode::Char('x') if key.modifiers.contains(KeyModifiers::CONTROL) && self.mode != EditMode::View => {
+                if let Some(range) = self.selection() {
+                    self.copy(range.clone(), clipboard, colors)?;
+                    edit::delete_range(self, range);
+                }
+            }
+            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) && self.mode != EditMode::View => {
+                edit::paste(self, clipboard, false);
+            }
+            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::ALT) && self.mode != EditMode::View => {
+                edit::paste(self, clipboard, true);
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::ALT) => self.load_clipboard(clipboard, colors)?,
 
 
             // NAVIGATION CONTROLS
+            KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => {
+                if let Some(pos) = self.jumps.back(self.cursor_pos) {
+                    self.move_cursor_to(pos);
+                }
+            }
+            KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => {
+                if let Some(pos) = self.jumps.forward(self.cursor_pos) {
+                    self.move_cursor_to(pos);
+                }
+            }
             KeyCode::Up => navigation::move_up(self),
             KeyCode::Down => navigation::move_down(self),
             KeyCode::Left => navigation::move_left(self),
@@ -141,9 +366,39 @@ impl MicroHex {
             }
             KeyCode::Home => navigation::move_home(self),
             KeyCode::End => navigation::move_end(self),
+            KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => self.go_to_offset(colors)?,
+            KeyCode::Char('g') if self.mode == EditMode::View => self.go_to_offset(colors)?,
+
+
+            // BOOKMARKS
+            KeyCode::Char('m') if key.modifiers.contains(KeyModifiers::ALT) => self.add_bookmark()?,
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::ALT) => self.show_bookmarks(colors)?,
+            KeyCode::Char('m') if self.mode == EditMode::View => {
+                if let Some(name) = self.read_char()? {
+                    self.bookmarks.set(&name.to_string(), self.cursor_pos, String::new());
+                }
+            }
+            KeyCode::Char('\'') if self.mode == EditMode::View => {
+                if let Some(name) = self.read_char()? {
+                    match self.bookmarks.get(&name.to_string()) {
+                        Some(mark) => self.jump_to(mark.pos),
+                        None => {
+                            let message = format!("Mark '{name}' is not set. Press any key to continue...");
+                            display::show_message(self, &message, colors)?;
+                        }
+                    }
+                }
+            }
 
 
             // EDITING CONTROLS
+            KeyCode::Insert => {
+                self.insert_mode = !self.insert_mode;
+                self.pending_digits = 0;
+            }
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::ALT) && self.mode != EditMode::View => {
+                self.fill(colors)?;
+            }
             KeyCode::Char(c) if !matches!(self.mode, EditMode::View) => {
                 edit::edit_byte(self, c);
             }
@@ -151,7 +406,11 @@ impl MicroHex {
                 edit::delete_prev_byte(self);
             }
             KeyCode::Backspace if !matches!(self.mode, EditMode::View) => {
-                edit::backspace(self);
+                if self.insert_mode {
+                    edit::delete_before_cursor(self);
+                } else {
+                    edit::backspace(self);
+                }
             }
 
             // SEARCH MODE
@@ -164,8 +423,7 @@ impl MicroHex {
                         self.search_state = search::SearchState::new(&self.bytes, pattern);
                         
                         if let Some(ref state) = self.search_state {
-                            self.cursor_pos = state.current_position();
-                            navigation::scroll_to_cursor(self);
+                            self.jump_to(state.current_position());
                             // Search info now displays persistently in help bar
                         } else {
                             display::show_message(
@@ -193,8 +451,8 @@ impl MicroHex {
             KeyCode::Char('n') if self.search_state.is_some() => {
                 if let Some(ref mut state) = self.search_state {
                     state.next_match();
-                    self.cursor_pos = state.current_position();
-                    navigation::scroll_to_cursor(self);
+                    let pos = state.current_position();
+                    self.jump_to(pos);
                     // Match info displays in help bar automatically
                 }
             }
@@ -203,20 +461,299 @@ impl MicroHex {
             KeyCode::Char('N') if self.search_state.is_some() => {
                 if let Some(ref mut state) = self.search_state {
                     state.prev_match();
-                    self.cursor_pos = state.current_position();
-                    navigation::scroll_to_cursor(self);
+                    let pos = state.current_position();
+                    self.jump_to(pos);
                     // Match info displays in help bar automatically
                 }
             }
 
             _ => {}
         }
-        Ok(false)
+        Ok(())
+    }
+
+    /// Show `items` in a popup list and let the user pick one. Enter selects, Esc closes the list without a choice,
+    /// and any of `keys` returns that key along with the entry it was pressed on.
+    pub fn select_from_list(
+        &self,
+        title: &str,
+        items: &[String],
+        initial: usize,
+        keys: &[char],
+        footer: &str,
+        colors: &ColorConfig,
+    ) -> io::Result<Option<ListAction>> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let page = self.lines_per_page;
+        let mut selected = initial.min(items.len() - 1);
+        loop {
+            display::draw_list_popup(self, title, items, selected, footer, colors)?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Up => selected = selected.saturating_sub(1),
+                    KeyCode::Down => selected = (selected + 1).min(items.len() - 1),
+                    KeyCode::PageUp => selected = selected.saturating_sub(page),
+                    KeyCode::PageDown => selected = (selected + page).min(items.len() - 1),
+                    KeyCode::Home => selected = 0,
+                    KeyCode::End => selected = items.len() - 1,
+                    KeyCode::Enter => return Ok(Some(ListAction::Select(selected))),
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Char(c) if keys.contains(&c) => return Ok(Some(ListAction::Key(c, selected))),
+                    _ => {}
+                }
+            }
+        }
     }
 
-    fn prompt(&self, message: &str) -> io::Result<Option<String>> {
+    /// List every change made to the file since it was opened or saved. Enter jumps to a change and `r` puts the
+    /// original bytes back, as an undoable edit, leaving the list open on the next change.
+    fn show_changes(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        let footer = "Up/Down: Select   Enter: Jump to change   r: Revert change   Esc: Close";
+        let mut selected = 0;
+        loop {
+            let changes = self.bytes.changes();
+            if changes.is_empty() {
+                return display::show_message(self, "No changes. Press any key to continue...", colors);
+            }
+            let entries = edit::change_entries(self, &changes);
+            match self.select_from_list("Changes", &entries, selected, &['r'], footer, colors)? {
+                Some(ListAction::Select(index)) => {
+                    self.jump_to(changes[index].current.start);
+                    return Ok(());
+                }
+                Some(ListAction::Key('r', index)) => {
+                    edit::revert_change(self, &changes[index]);
+                    selected = index;
+                }
+                Some(ListAction::Key(..)) | None => return Ok(()),
+            }
+        }
+    }
+
+    /// Ask for a new value for the selected inspector field and write its bytes at the cursor.
+    fn edit_inspector_value(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        let field = inspector::fields()[self.inspector_row];
+        let current = field.decode(&self.inspector_bytes()).unwrap_or_default();
+        let Some(input) = self.prompt(&format!("New {} value (now {}): ", field.name(), current))? else {
+            return Ok(());
+        };
+        match field.encode(&input) {
+            Ok(bytes) => edit::overwrite_at_cursor(self, &bytes),
+            Err(e) => display::show_message(self, &format!("{e}. Press any key to continue..."), colors)?,
+        }
+        Ok(())
+    }
+
+    /// The bytes in a selection, reading virtual bytes past the end of the file as zero like they're shown.
+    fn selected_bytes(&self, range: Range<usize>) -> Vec<u8> {
+        let mut bytes = self.bytes.read_range(range.start.min(self.bytes.len())..range.end.min(self.bytes.len()));
+        bytes.resize(range.len(), 0);
+        bytes
+    }
+
+    /// Put the bytes in `range` on the clipboard, and on the system clipboard too unless that's turned off or
+    /// the selection is too big to send through the terminal.
+    fn copy(&mut self, range: Range<usize>, clipboard: &mut Vec<u8>, colors: &ColorConfig) -> io::Result<()> {
+        *clipboard = self.selected_bytes(range);
+        if let Some(text) = self.settings.copy_format.format(clipboard)
+            && !clipboard::copy_to_system(&text)?
+        {
+            let message = format!(
+                "Copied {} byte(s), but too many for the system clipboard. Press any key to continue...",
+                clipboard.len()
+            );
+            display::show_message(self, &message, colors)?;
+        }
+        Ok(())
+    }
+
+    /// Ask for text to put on the clipboard: a hex string, a C array, a Python bytes literal, base64 or plain text.
+    fn load_clipboard(&mut self, clipboard: &mut Vec<u8>, colors: &ColorConfig) -> io::Result<()> {
+        let Some(input) = self.prompt("Clipboard (hex, {C array}, b'python', base64 or text; prefix hex:/c:/python:/base64:/text: to force): ")? else {
+            return Ok(());
+        };
+        self.set_clipboard_text(&input, clipboard, colors)
+    }
+
+    /// Read `text` as bytes onto the clipboard and say what came of it.
+    fn set_clipboard_text(&mut self, text: &str, clipboard: &mut Vec<u8>, colors: &ColorConfig) -> io::Result<()> {
+        let message = match clipboard::parse_text(text) {
+            Ok((bytes, format)) => {
+                *clipboard = bytes;
+                format!(
+                    "Clipboard holds {} byte(s) read as {}. ^V pastes over, Alt+V inserts. Press any key to continue...",
+                    clipboard.len(),
+                    format.name()
+                )
+            }
+            Err(e) => format!("{e}. Press any key to continue..."),
+        };
+        display::show_message(self, &message, colors)
+    }
+
+    /// Handle text pasted into the terminal (a bracketed paste). In the hex pane it's read like clipboard text
+    /// and inserted or written over the bytes at the cursor, depending on the insert mode; in the text pane each character is written in the pane's
+    /// encoding. In view mode it goes onto the clipboard instead, ready to be pasted with ^V or Alt+V.
+    pub fn paste_text(&mut self, text: &str, clipboard: &mut Vec<u8>, colors: &ColorConfig) -> io::Result<()> {
+        let bytes = match self.mode {
+            EditMode::View => return self.set_clipboard_text(text, clipboard, colors),
+            EditMode::EditHex => clipboard::parse_text(text).map(|(bytes, _)| bytes),
+            EditMode::EditAscii => {
+                let encoding = self.settings.encoding;
+                text.chars()
+                    .map(|c| encoding.encode(c).ok_or_else(|| format!("Can't write {c:?} in {}", encoding.label())))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|chars| chars.concat())
+            }
+        };
+        match bytes {
+            Ok(bytes) => edit::paste(self, &bytes, self.insert_mode),
+            Err(e) => display::show_message(self, &format!("{e}. Press any key to continue..."), colors)?,
+        }
+        Ok(())
+    }
+
+    /// Fill the selection, or a number of bytes from the cursor, with a byte, a pattern, a counter or random
+    /// bytes. Without a selection the bytes are inserted or written over depending on the insert mode.
+    fn fill(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        let (start, len, insert) = match self.selection() {
+            Some(range) => (range.start, range.len(), false),
+            None => {
+                let Some(input) = self.prompt("Fill how many bytes from the cursor? [64, 0x100, 1024*4, 10%]: ")? else {
+                    return Ok(());
+                };
+                let limit = self.bytes.len().saturating_add(MAX_FILL_GROWTH);
+                match goto::evaluate(&input, 0, self.bytes.len()) {
+                    Ok(len) if len <= limit => (self.cursor_pos, len, self.insert_mode),
+                    Ok(len) => {
+                        let message = format!("Can't fill {len} bytes, the limit is {limit}. Press any key to continue...");
+                        return display::show_message(self, &message, colors);
+                    }
+                    Err(e) => return display::show_message(self, &format!("{e}. Press any key to continue..."), colors),
+                }
+            }
+        };
+        let Some(end) = start.checked_add(len) else {
+            return display::show_message(self, "Fill reaches past the largest offset. Press any key to continue...", colors);
+        };
+        let question = format!("Fill {len} byte(s) with [00 | de ad be ef | count:start[,step] | random:seed]: ");
+        let Some(input) = self.prompt(&question)? else {
+            return Ok(());
+        };
+        match edit::Fill::parse(&input) {
+            Ok(fill) => edit::fill(self, start..end, &fill, insert),
+            Err(e) => display::show_message(self, &format!("{e}. Press any key to continue..."), colors)?,
+        }
+        Ok(())
+    }
+
+    /// Bytes from the cursor onwards, as many as the longest inspector field needs.
+    pub fn inspector_bytes(&self) -> Vec<u8> {
+        let end = (self.cursor_pos + inspector::MAX_FIELD_LEN).min(self.bytes.len());
+        self.bytes.read_range(self.cursor_pos.min(end)..end)
+    }
+
+    /// Move the cursor to `pos` (or the last byte, if it's past the end), remembering where it was so
+    /// the jump can be gone back on.
+    pub fn jump_to(&mut self, pos: usize) {
+        if pos != self.cursor_pos {
+            self.jumps.record(self.cursor_pos);
+        }
+        self.move_cursor_to(pos);
+    }
+
+    fn move_cursor_to(&mut self, pos: usize) {
+        self.cursor_pos = pos.min(self.view_len() - 1);
+        self.pending_digits = 0;
+        navigation::scroll_to_cursor(self);
+    }
+
+    /// Wait for the next key and return it if it's a character, for two-key commands like `m<char>`.
+    fn read_char(&self) -> io::Result<Option<char>> {
+        loop {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                return Ok(match key.code {
+                    KeyCode::Char(c) => Some(c),
+                    _ => None,
+                });
+            }
+        }
+    }
+
+    /// Ask for a name and comment and bookmark the cursor position.
+    fn add_bookmark(&mut self) -> io::Result<()> {
+        let Some(name) = self.prompt(&format!("Bookmark 0x{:X} as: ", self.cursor_pos))? else {
+            return Ok(());
+        };
+        let comment = self.prompt("Comment (optional): ")?.unwrap_or_default();
+        self.bookmarks.set(name.trim(), self.cursor_pos, comment);
+        Ok(())
+    }
+
+    /// List marks and bookmarks. Enter jumps to one, `c` changes its comment and `d` deletes it.
+    fn show_bookmarks(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        let footer = "Up/Down: Select   Enter: Jump   c: Edit comment   d: Delete   Esc: Close";
+        let mut selected = 0;
+        loop {
+            if self.bookmarks.is_empty() {
+                let message = "No bookmarks. Set a mark with m<key> in view mode or add a bookmark with Alt+M. Press any key to continue...";
+                return display::show_message(self, message, colors);
+            }
+            let entries: Vec<String> = self
+                .bookmarks
+                .list()
+                .iter()
+                .map(|b| format!("{:<16} 0x{:08X}  {}", b.name, b.pos, b.comment))
+                .collect();
+            match self.select_from_list("Bookmarks", &entries, selected, &['c', 'd'], footer, colors)? {
+                Some(ListAction::Select(index)) => {
+                    self.jump_to(self.bookmarks.list()[index].pos);
+                    return Ok(());
+                }
+                Some(ListAction::Key('c', index)) => {
+                    let current = self.bookmarks.list()[index].comment.clone();
+                    if let Some(comment) = self.prompt_with("Comment: ", &current)? {
+                        self.bookmarks.set_comment(index, comment);
+                    }
+                    selected = index;
+                }
+                Some(ListAction::Key('d', index)) => {
+                    self.bookmarks.remove(index);
+                    selected = index;
+                }
+                Some(ListAction::Key(..)) | None => return Ok(()),
+            }
+        }
+    }
+
+    /// Ask for an offset expression and move the cursor there. Offsets past the end land on the last byte.
+    fn go_to_offset(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        let Some(input) = self.prompt("Go to offset [123, 0x7B, 0o173, 0b1111011, +16, -0x10, 0x1000+4*8, 50%]: ")? else {
+            return Ok(());
+        };
+        match goto::evaluate(&input, self.cursor_pos, self.bytes.len()) {
+            Ok(target) => self.jump_to(target),
+            Err(e) => {
+                display::show_message(self, &format!("{e}. Press any key to continue..."), colors)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn prompt(&self, message: &str) -> io::Result<Option<String>> {
+        self.prompt_with(message, "")
+    }
+
+    /// Like `prompt`, but starts with `initial` already typed in so it can be edited or accepted as is.
+    fn prompt_with(&self, message: &str, initial: &str) -> io::Result<Option<String>> {
         let mut stdout = io::stdout();
-        let mut input = String::new();
+        let mut input = String::from(initial);
         let prompt_row = (self.lines_per_page + 4) as u16;
         
         loop {
@@ -225,43 +762,184 @@ impl MicroHex {
             write!(stdout, "{}{}", message, input)?;
             stdout.flush()?;
             
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Enter => {
-                            return if input.is_empty() {
-                                Ok(None)
-                            } else {
-                                Ok(Some(input))
-                            };
-                        }
-                        KeyCode::Esc => {
-                            return Ok(None);
-                        }
-                        KeyCode::Backspace => {
-                            input.pop();
-                        }
-                        KeyCode::Char(c) => {
-                            input.push(c);
-                        }
-                        _ => {}
+            let event = event::read()?;
+            if let Event::Paste(text) = &event {
+                // The prompt is a single line, so pasted line breaks become spaces
+                input.push_str(&text.replace(['\r', '\n'], " "));
+            }
+            if let Event::Key(key) = event
+                && key.kind == KeyEventKind::Press
+            {
+                match key.code {
+                    KeyCode::Enter => {
+                        return if input.is_empty() {
+                            Ok(None)
+                        } else {
+                            Ok(Some(input))
+                        };
+                    }
+                    KeyCode::Esc => {
+                        return Ok(None);
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => {
+                        input.push(c);
                     }
+                    _ => {}
                 }
             }
         }
     }
 
-    fn save(&mut self) -> io::Result<()> {
-        // Trim trailing null bytes (0x00) before saving, but always leave at least one byte
-        let mut data = self.bytes.clone();
-        while data.len() > 1 && data.last() == Some(&0) {
-            data.pop();
+    /// Write the swap file if it has fallen behind the buffer and enough time has passed since the last write.
+    /// Once there's nothing left unsaved the swap file is removed instead. Failures are ignored and retried
+    /// on the next call, since a missing swap file must never get in the way of editing.
+    pub fn update_swap(&mut self) {
+        let current = self.history.current();
+        if self.settings.swap_interval == 0
+            || current == self.swap_state
+            || self.last_swap_write.elapsed() < Duration::from_secs(self.settings.swap_interval)
+        {
+            return;
         }
-        fs::write(&self.filename, &data)?;
-        // Update original_bytes and bytes to match the saved state
-        self.original_bytes = data.clone();
-        self.bytes = data;
+        let written = if self.modified {
+            journal::write_swap(self)
+        } else {
+            journal::remove_swap(Path::new(&self.filename))
+        };
+        if written.is_ok() {
+            self.swap_state = current;
+            self.last_swap_write = Instant::now();
+        }
+    }
+
+    /// True if the file on disk is no longer the one we loaded or last saved.
+    fn changed_on_disk(&self) -> bool {
+        let stamp = FileStamp::of(Path::new(&self.filename)).ok();
+        stamp.is_some() && stamp != self.disk_stamp
+    }
+
+    /// Every so often while idle, check whether another program changed the file and offer to reload it.
+    /// Returns true if a prompt was shown, so the screen needs redrawing.
+    pub fn check_external_change(&mut self, colors: &ColorConfig) -> io::Result<bool> {
+        if !self.settings.watch_file || self.last_disk_check.elapsed() < WATCH_INTERVAL {
+            return Ok(false);
+        }
+        self.last_disk_check = Instant::now();
+        let stamp = FileStamp::of(Path::new(&self.filename)).ok();
+        if stamp.is_none() || stamp == self.disk_stamp || stamp == self.ignored_stamp {
+            return Ok(false);
+        }
+
+        let question = if self.modified {
+            "File changed on disk. Reload it and lose your unsaved changes? (y/n): "
+        } else {
+            "File changed on disk. Reload it? (y/n): "
+        };
+        if self.prompt(question)?.is_some_and(|ans| ans.to_lowercase() == "y") {
+            if let Err(e) = self.reload() {
+                let message = format!("Could not reload '{}': {}. Press any key to continue...", self.filename, e);
+                display::show_message(self, &message, colors)?;
+            }
+        } else {
+            // Don't ask again about this version of the file; saving over it will still warn
+            self.ignored_stamp = stamp;
+        }
+        Ok(true)
+    }
+
+    /// Re-read the file from disk, keeping the cursor and view where they were as far as the new contents allow.
+    /// The undo history describes the old contents, so it starts over.
+    fn reload(&mut self) -> io::Result<()> {
+        let path = PathBuf::from(&self.filename);
+        self.bytes = PieceTable::open(&path)?;
+        self.disk_stamp = FileStamp::of(&path).ok();
+        self.ignored_stamp = None;
+        self.history = UndoTree::new();
         self.modified = false;
+        self.search_state = None;
+        self.pending_digits = 0;
+        self.virtual_len = if self.bytes.is_empty() { 1 } else { 0 };
+        self.cursor_pos = self.cursor_pos.min(self.view_len() - 1);
+        navigation::scroll_to_cursor(self);
+        let _ = journal::remove_swap(&path);
+        self.swap_state = ROOT;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Save, showing any error in the UI instead of giving up on the session. Returns whether it worked.
+    pub fn save_reporting_errors(&mut self, colors: &ColorConfig) -> io::Result<bool> {
+        let path = PathBuf::from(&self.filename);
+        self.write_to_path(&path, colors)
+    }
+
+    /// Ask for a new path and write the buffer there, switching the editor over to it on success.
+    fn save_as(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        let Some(input) = self.prompt_with("Save as: ", &self.filename)? else {
+            return Ok(());
+        };
+        let path = PathBuf::from(input.trim());
+        if path != Path::new(&self.filename) && path.exists() {
+            let question = format!("'{}' already exists. Overwrite? (y/n): ", path.display());
+            if self.prompt(&question)?.is_none_or(|ans| ans.to_lowercase() != "y") {
+                return Ok(());
+            }
+        }
+        self.write_to_path(&path, colors)?;
+        Ok(())
+    }
+
+    /// Write the buffer to `path` and make it the file being edited. I/O errors are shown as a message and
+    /// leave the editor exactly as it was. Returns whether the write succeeded.
+    fn write_to_path(&mut self, path: &Path, colors: &ColorConfig) -> io::Result<bool> {
+        if path == Path::new(&self.filename) && self.changed_on_disk() {
+            let question = "File was changed by another program since it was opened. Overwrite it anyway? (y/n): ";
+            if self.prompt(question)?.is_none_or(|ans| ans.to_lowercase() != "y") {
+                return Ok(false);
+            }
+        }
+        match self.save(path) {
+            Ok(()) => {
+                // The saved file becomes the new original, so change highlighting starts fresh. If it can't
+                // be read back the old buffer still holds exactly what was written, so keep editing that.
+                match PieceTable::open(path) {
+                    Ok(bytes) => self.bytes = bytes,
+                    Err(e) => {
+                        let message = format!(
+                            "Saved '{}', but could not reopen it: {}. Press any key to continue...",
+                            path.display(),
+                            e
+                        );
+                        display::show_message(self, &message, colors)?;
+                    }
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                let message = format!("Could not save '{}': {}. Press any key to continue...", path.display(), e);
+                display::show_message(self, &message, colors)?;
+                Ok(false)
+            }
+        }
+    }
+
+    fn save(&mut self, path: &Path) -> io::Result<()> {
+        // Unmodified regions are streamed from the file we're about to replace, so the new contents
+        // are written next to it first and only swapped into place once the write has succeeded
+        save::write_atomic(path, self.settings.backup, |writer| self.bytes.write_to(writer))?;
+
+        let old_path = PathBuf::from(&self.filename);
+        self.filename = path.to_string_lossy().into_owned();
+        self.history.mark_saved();
+        self.modified = false;
+        self.disk_stamp = FileStamp::of(path).ok();
+        self.ignored_stamp = None;
+        // Pending edits are on disk now, so neither the old nor the new path needs a swap file
+        let _ = journal::remove_swap(&old_path);
+        let _ = journal::remove_swap(path);
+        self.swap_state = self.history.current();
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Character encodings for the text pane.
+//!
+//! Decodes a line of bytes into one glyph per byte cell and encodes typed characters back into bytes.
+//! Single-byte encodings map every byte to its own character. In UTF-8 and UTF-16 a character is shown in the
+//! cell of its first byte and the cells of its other bytes are left blank, so the text pane stays lined up
+//! with the data pane.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Ascii,
+    Latin1,
+    Cp437, // IBM PC
+    Cp037, // EBCDIC, US/Canada
+    Utf8,
+    Utf16le,
+    Utf16be,
+}
+
+/// What to show in one cell of the text pane.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Glyph {
+    Char(char),
+    Wide(char),   // A double-width character, drawn over this cell and the next
+    Continuation, // A later byte of a character shown in an earlier cell
+    Invalid,      // Not a printable character in this encoding
+}
+
+impl Encoding {
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ASCII",
+            Encoding::Latin1 => "LATIN-1",
+            Encoding::Cp437 => "CP437",
+            Encoding::Cp037 => "CP037",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16le => "UTF-16LE",
+            Encoding::Utf16be => "UTF-16BE",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Encoding::Ascii => Encoding::Latin1,
+            Encoding::Latin1 => Encoding::Cp437,
+            Encoding::Cp437 => Encoding::Cp037,
+            Encoding::Cp037 => Encoding::Utf8,
+            Encoding::Utf8 => Encoding::Utf16le,
+            Encoding::Utf16le => Encoding::Utf16be,
+            Encoding::Utf16be => Encoding::Ascii,
+        }
+    }
+
+    /// Bytes around a line needed to decode the characters that cross its edges.
+    pub fn context(self) -> usize {
+        match self {
+            Encoding::Utf8 | Encoding::Utf16le | Encoding::Utf16be => 3,
+            _ => 0,
+        }
+    }
+
+    /// One glyph for each of the `count` bytes starting at `window[start]`. `window` holds the bytes around them
+    /// too (see `context`), and `window_pos` is where it starts in the file, which UTF-16 needs to find the
+    /// start of each code unit.
+    pub fn glyphs(self, window: &[u8], window_pos: usize, start: usize, count: usize) -> Vec<Glyph> {
+        let mut glyphs = vec![Glyph::Invalid; count];
+        let mut place = |first: usize, len: usize, c: Option<char>| {
+            for i in first..first + len {
+                if i < start || i >= start + count {
+                    continue;
+                }
+                glyphs[i - start] = match c {
+                    Some(_) if i != first => Glyph::Continuation,
+                    Some(c) => printable(c, len > 1 && i + 1 < start + count),
+                    None => Glyph::Invalid,
+                };
+            }
+        };
+        match self {
+            Encoding::Ascii | Encoding::Latin1 | Encoding::Cp437 | Encoding::Cp037 => {
+                for (i, &b) in window.iter().enumerate() {
+                    place(i, 1, self.single_byte_char(b));
+                }
+            }
+            Encoding::Utf8 => {
+                let mut i = 0;
+                while i < window.len() {
+                    let len = match window[i] {
+                        0xC0..=0xDF => 2,
+                        0xE0..=0xEF => 3,
+                        0xF0..=0xF7 => 4,
+                        _ => 1,
+                    };
+                    match window.get(i..i + len).and_then(|bytes| std::str::from_utf8(bytes).ok()) {
+                        Some(text) => {
+                            place(i, len, text.chars().next());
+                            i += len;
+                        }
+                        None => {
+                            place(i, 1, None);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            Encoding::Utf16le | Encoding::Utf16be => {
+                // Code units start at even offsets in the file
+                let mut i = window_pos % 2;
+                let unit = |i: usize| {
+                    let bytes = [*window.get(i)?, *window.get(i + 1)?];
+                    Some(if self == Encoding::Utf16le { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+                };
+                while let Some(first) = unit(i) {
+                    let (len, c) = match (first, unit(i + 2)) {
+                        (0xD800..=0xDBFF, Some(second @ 0xDC00..=0xDFFF)) => {
+                            (4, char::decode_utf16([first, second]).next().and_then(Result::ok))
+                        }
+                        _ => (2, char::from_u32(first as u32)),
+                    };
+                    place(i, len, c);
+                    i += len;
+                }
+            }
+        }
+        glyphs
+    }
+
+    /// Bytes that store `c`, or None if this encoding can't represent it.
+    pub fn encode(self, c: char) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Ascii => c.is_ascii().then(|| vec![c as u8]),
+            Encoding::Latin1 => u8::try_from(c as u32).ok().map(|b| vec![b]),
+            Encoding::Cp437 | Encoding::Cp037 => {
+                let table = if self == Encoding::Cp437 { &CP437 } else { &CP037 };
+                table.iter().position(|&t| t == c && c != '\0').map(|b| vec![b as u8])
+            }
+            Encoding::Utf8 => Some(c.to_string().into_bytes()),
+            Encoding::Utf16le => Some(c.encode_utf16(&mut [0; 2]).iter().flat_map(|u| u.to_le_bytes()).collect()),
+            Encoding::Utf16be => Some(c.encode_utf16(&mut [0; 2]).iter().flat_map(|u| u.to_be_bytes()).collect()),
+        }
+    }
+
+    /// The printable character for `b` in a single-byte encoding, if there is one.
+    fn single_byte_char(self, b: u8) -> Option<char> {
+        let c = match self {
+            Encoding::Ascii => b as char,
+            Encoding::Latin1 => b as char,
+            Encoding::Cp437 => CP437[b as usize],
+            _ => CP037[b as usize],
+        };
+        let printable = match self {
+            Encoding::Ascii => b.is_ascii_graphic() || b == b' ',
+            Encoding::Latin1 => !c.is_control() && b != 0xAD,
+            _ => c != '\0',
+        };
+        printable.then_some(c)
+    }
+}
+
+/// How to show `c`. Control and zero-width characters would upset the layout, so they count as invalid.
+/// Double-width characters are only drawn when `room_for_wide` says the next cell can be drawn over.
+fn printable(c: char, room_for_wide: bool) -> Glyph {
+    let code = c as u32;
+    let zero_width = matches!(
+        code,
+        0x0300..=0x036F
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x200B..=0x200F
+            | 0x2028..=0x202E
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F
+            | 0xFE20..=0xFE2F
+            | 0xFEFF
+    );
+    let wide = matches!(
+        code,
+        0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD
+    );
+    if c.is_control() || zero_width || (wide && !room_for_wide) {
+        Glyph::Invalid
+    } else if wide {
+        Glyph::Wide(c)
+    } else {
+        Glyph::Char(c)
+    }
+}
+
+const CP437: [char; 256] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', // 00
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼', // 10
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', // 20
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?', // 30
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', // 40
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_', // 50
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', // 60
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂', // 70
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', // 80
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', // 90
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', // A0
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', // B0
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', // C0
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', // D0
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', // E0
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}', // F0
+];
+
+const CP037: [char; 256] = [
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 00
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 10
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 20
+    '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 30
+    ' ', '\u{a0}', 'â', 'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '¢', '.', '<', '(', '+', '|', // 40
+    '&', 'é', 'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', 'ß', '!', '$', '*', ')', ';', '¬', // 50
+    '-', '/', 'Â', 'Ä', 'À', 'Á', 'Ã', 'Å', 'Ç', 'Ñ', '¦', ',', '%', '_', '>', '?', // 60
+    'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#', '@', '\'', '=', '"', // 70
+    'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ', '±', // 80
+    '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤', // 90
+    'µ', '~', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®', // A0
+    '^', '£', '¥', '·', '©', '§', '¶', '¼', '½', '¾', '[', ']', '¯', '¨', '´', '×', // B0
+    '{', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\0', 'ô', 'ö', 'ò', 'ó', 'õ', // C0
+    '}', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', '¹', 'û', 'ü', 'ù', 'ú', 'ÿ', // D0
+    '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', 'Ö', 'Ò', 'Ó', 'Õ', // E0
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', 'Ü', 'Ù', 'Ú', '\0', // F0
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Glyph::{Char, Continuation, Invalid, Wide};
+
+    /// Glyphs for `count` bytes of `data` from `pos`, decoded with the same context the text pane uses.
+    fn line(encoding: Encoding, data: &[u8], pos: usize, count: usize) -> Vec<Glyph> {
+        let window_start = pos.saturating_sub(encoding.context());
+        let window_end = (pos + count + encoding.context()).min(data.len());
+        encoding.glyphs(&data[window_start..window_end], window_start, pos - window_start, count)
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn utf8_characters_crossing_line_edges() {
+        let data = "ab€cd".as_bytes();
+        assert_eq!(line(Encoding::Utf8, data, 0, 3), [Char('a'), Char('b'), Char('€')]);
+        assert_eq!(line(Encoding::Utf8, data, 3, 3), [Continuation, Continuation, Char('c')]);
+        // The last byte of a 4-byte character is still just within the context
+        let data = "a😀b".as_bytes();
+        assert_eq!(line(Encoding::Utf8, data, 4, 2), [Continuation, Char('b')]);
+    }
+
+    #[test]
+    fn broken_utf8_is_shown_byte_by_byte() {
+        assert_eq!(line(Encoding::Utf8, &[0xff, b'a', 0x80, 0xc3], 0, 4), [Invalid, Char('a'), Invalid, Invalid]);
+        // A lead byte whose continuation is missing doesn't swallow the next character
+        assert_eq!(line(Encoding::Utf8, &[0xe2, 0x82, b'x'], 0, 3), [Invalid, Invalid, Char('x')]);
+    }
+
+    #[test]
+    fn utf16_follows_code_units_from_odd_offsets() {
+        let data = utf16le("ABCDEF");
+        assert_eq!(line(Encoding::Utf16le, &data, 4, 4), [Char('C'), Continuation, Char('D'), Continuation]);
+        assert_eq!(line(Encoding::Utf16le, &data, 5, 4), [Continuation, Char('D'), Continuation, Char('E')]);
+        // Without any context the half of a unit before the line is unknown
+        assert_eq!(Encoding::Utf16le.glyphs(&data[1..5], 1, 0, 4), [Invalid, Char('B'), Continuation, Invalid]);
+        let data: Vec<u8> = "AB".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(line(Encoding::Utf16be, &data, 0, 4), [Char('A'), Continuation, Char('B'), Continuation]);
+    }
+
+    #[test]
+    fn utf16_surrogate_pairs() {
+        let data = utf16le("A😀B");
+        assert_eq!(line(Encoding::Utf16le, &data, 0, 4), [Char('A'), Continuation, Wide('😀'), Continuation]);
+        // Split across lines, the second half continues the character on the line before
+        assert_eq!(line(Encoding::Utf16le, &data, 4, 4), [Continuation, Continuation, Char('B'), Continuation]);
+        // A high surrogate without its low half isn't a character
+        let lone = [0x3d, 0xd8, b'A', 0];
+        assert_eq!(line(Encoding::Utf16le, &lone, 0, 4), [Invalid, Invalid, Char('A'), Continuation]);
+    }
+
+    #[test]
+    fn wide_glyphs_need_the_next_cell() {
+        let data = "中x".as_bytes();
+        assert_eq!(line(Encoding::Utf8, data, 0, 4), [Wide('中'), Continuation, Continuation, Char('x')]);
+        // Nothing to draw over at the end of a line
+        assert_eq!(line(Encoding::Utf8, data, 0, 1), [Invalid]);
+        let data = utf16le("中");
+        assert_eq!(line(Encoding::Utf16le, &data, 0, 1), [Invalid]);
+        assert_eq!(line(Encoding::Utf16le, &data, 0, 2), [Wide('中'), Continuation]);
+        // Zero-width characters would throw the layout off
+        assert_eq!(line(Encoding::Utf8, "\u{200b}".as_bytes(), 0, 3), [Invalid, Continuation, Continuation]);
+    }
+
+    #[test]
+    fn code_page_tables_round_trip() {
+        for encoding in [Encoding::Ascii, Encoding::Latin1, Encoding::Cp437, Encoding::Cp037] {
+            for b in 0..=255u8 {
+                if let Some(c) = encoding.single_byte_char(b) {
+                    assert_eq!(encoding.encode(c), Some(vec![b]), "{} byte {b:#04x}", encoding.label());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn code_page_characters() {
+        let decode = |encoding: Encoding, bytes: &[u8]| -> String {
+            bytes.iter().map(|&b| encoding.single_byte_char(b).unwrap_or('.')).collect()
+        };
+        assert_eq!(decode(Encoding::Cp037, &[0xc8, 0x85, 0x93, 0x93, 0x96, 0x40, 0xf1, 0x5a]), "Hello 1!");
+        assert_eq!(decode(Encoding::Cp037, &[0x00, 0x25, 0xff]), "...");
+        assert_eq!(decode(Encoding::Cp437, &[0x01, 0x41, 0xb0, 0xc9, 0xe1, 0xfb]), "☺A░╔ß√");
+        assert_eq!(decode(Encoding::Cp437, &[0x00]), ".");
+        assert_eq!(Encoding::Cp037.encode('€'), None);
+        assert_eq!(Encoding::Ascii.encode('é'), None);
+        assert_eq!(Encoding::Latin1.encode('é'), Some(vec![0xe9]));
+        assert_eq!(Encoding::Utf8.encode('€'), Some(vec![0xe2, 0x82, 0xac]));
+        assert_eq!(Encoding::Utf16be.encode('😀'), Some(vec![0xd8, 0x3d, 0xde, 0x00]));
+    }
+}
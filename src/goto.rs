@@ -0,0 +1,173 @@
+//! Go-to-offset expressions.
+//!
+//! Parses what's typed at the go-to prompt: decimal, `0x` hex, `0o` octal and `0b` binary numbers,
+//! `+`, `-`, `*`, `/` and parentheses, and `N%` for a percentage of the file size.
+//! A leading `+` or `-` makes the whole expression relative to the cursor.
+
+/// Work out the offset `input` refers to, given the cursor position and file length.
+pub fn evaluate(input: &str, cursor_pos: usize, file_len: usize) -> Result<usize, String> {
+    let input = input.trim();
+    let mut parser = Parser {
+        chars: input.chars().filter(|c| !c.is_whitespace()).collect(),
+        pos: 0,
+        file_len: file_len as i128,
+    };
+    let relative = matches!(parser.peek(), Some('+' | '-'));
+    let value = parser.expression()?;
+    if let Some(c) = parser.peek() {
+        return Err(format!("Unexpected '{c}'"));
+    }
+
+    let target = if relative { cursor_pos as i128 + value } else { value };
+    if target < 0 {
+        return Err(format!("Offset {target} is before the start of the file"));
+    }
+    usize::try_from(target).map_err(|_| String::from("Offset is too large"))
+}
+
+struct Parser {
+    chars: Vec<char>, // Input with whitespace removed
+    pos: usize,
+    file_len: i128, // What 100% means
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<i128, String> {
+        let mut value = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value.checked_add(rhs) } else { value.checked_sub(rhs) }
+                .ok_or("Number too large")?;
+        }
+        Ok(value)
+    }
+
+    /// term := factor (('*' | '/') factor)*
+    fn term(&mut self) -> Result<i128, String> {
+        let mut value = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            value = if op == '*' {
+                value.checked_mul(rhs).ok_or("Number too large")?
+            } else {
+                value.checked_div(rhs).ok_or("Division by zero")?
+            };
+        }
+        Ok(value)
+    }
+
+    /// factor := ('+' | '-') factor | '(' expression ')' | number ['%']
+    fn factor(&mut self) -> Result<i128, String> {
+        match self.peek() {
+            Some('+') => {
+                self.pos += 1;
+                self.factor()
+            }
+            Some('-') => {
+                self.pos += 1;
+                Ok(-self.factor()?)
+            }
+            Some('(') => {
+                self.pos += 1;
+                let value = self.expression()?;
+                if self.peek() != Some(')') {
+                    return Err(String::from("Missing ')'"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let value = self.number()?;
+                if self.peek() == Some('%') {
+                    self.pos += 1;
+                    return Ok(value.checked_mul(self.file_len).ok_or("Number too large")? / 100);
+                }
+                Ok(value)
+            }
+            Some(c) => Err(format!("Unexpected '{c}'")),
+            None => Err(String::from("Expected a number")),
+        }
+    }
+
+    /// A decimal number, or hex/octal/binary with a `0x`/`0o`/`0b` prefix. `_` can be used to group digits.
+    fn number(&mut self) -> Result<i128, String> {
+        let radix = match (self.peek(), self.chars.get(self.pos + 1).map(|c| c.to_ascii_lowercase())) {
+            (Some('0'), Some('x')) => 16,
+            (Some('0'), Some('o')) => 8,
+            (Some('0'), Some('b')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.pos += 2;
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().filter(|&&c| c != '_').collect();
+        if digits.is_empty() {
+            return Err(String::from("Expected digits after the prefix"));
+        }
+        i128::from_str_radix(&digits, radix).map_err(|_| format!("'{digits}' is not a valid base {radix} number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_in_every_base() {
+        assert_eq!(evaluate("1234", 0, 0), Ok(1234));
+        assert_eq!(evaluate("0x1F", 0, 0), Ok(31));
+        assert_eq!(evaluate("0X1f", 0, 0), Ok(31));
+        assert_eq!(evaluate("0o17", 0, 0), Ok(15));
+        assert_eq!(evaluate("0b1010", 0, 0), Ok(10));
+        assert_eq!(evaluate("1_000_000", 0, 0), Ok(1_000_000));
+        assert_eq!(evaluate("0x_ff", 0, 0), Ok(255));
+    }
+
+    #[test]
+    fn arithmetic_follows_precedence() {
+        assert_eq!(evaluate("2 + 3 * 4", 0, 0), Ok(14));
+        assert_eq!(evaluate("(2 + 3) * 4", 0, 0), Ok(20));
+        assert_eq!(evaluate("10 - 4 - 3", 0, 0), Ok(3));
+        assert_eq!(evaluate("100 / 7", 0, 0), Ok(14));
+        assert_eq!(evaluate("0x100 - -0x10", 0, 0), Ok(0x110));
+    }
+
+    #[test]
+    fn percentages_are_of_the_file_length() {
+        assert_eq!(evaluate("50%", 0, 1000), Ok(500));
+        assert_eq!(evaluate("100%", 0, 1001), Ok(1001));
+        assert_eq!(evaluate("33%", 0, 10), Ok(3));
+        assert_eq!(evaluate("50% + 0x10", 0, 200), Ok(116));
+    }
+
+    #[test]
+    fn leading_sign_is_relative_to_the_cursor() {
+        assert_eq!(evaluate("+0x10", 100, 0), Ok(116));
+        assert_eq!(evaluate("-10", 100, 0), Ok(90));
+        assert_eq!(evaluate("  -(2 * 5)", 100, 0), Ok(90));
+        assert_eq!(evaluate("+10%", 100, 1000), Ok(200));
+        assert!(evaluate("-101", 100, 0).is_err());
+        assert!(evaluate("5 - 10", 100, 0).is_err());
+    }
+
+    #[test]
+    fn bad_input_is_rejected() {
+        for input in ["", "0x", "12g", "0b102", "(1 + 2", "1 + 2)", "1 / 0", "1 +", "abc", "%"] {
+            assert!(evaluate(input, 0, 100).is_err(), "{input:?} should be an error");
+        }
+        assert_eq!(evaluate("1 ) ", 0, 0), Err(String::from("Unexpected ')'")));
+        assert!(evaluate("0xffffffffffffffffffffffffffffffff", 0, 0).is_err());
+        assert!(evaluate("0x7fffffffffffffffffffffffffffffff * 2", 0, 0).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Branching undo history.
+//!
+//! Every recorded change becomes a node in a tree whose root is the file as it was opened. Undoing and then
+//! making a different edit starts a new branch instead of discarding the old one, so nothing is ever lost.
+//! Node ids are handed out in creation order, which doubles as the timeline used by `older`/`newer`.
+//!
+//! This module only tracks the shape of the history; applying changes to the buffer is done in `edit.rs`.
+
+use std::io::{self, Read, Write};
+
+use crate::editor::UndoState;
+use crate::journal::{read_change, read_u64, write_change, write_u64};
+
+/// Id of a state in the history. `ROOT` is the file as it was opened; every other id is the state reached
+/// by applying that node's change to its parent.
+pub type StateId = usize;
+pub const ROOT: StateId = 0;
+
+pub struct UndoNode {
+    pub change: UndoState,
+    pub parent: StateId,
+    redo_child: Option<StateId>, // Child that redo follows: the one most recently created or visited
+}
+
+pub struct UndoTree {
+    nodes: Vec<UndoNode>, // Node `id` is stored at nodes[id - 1]
+    root_redo_child: Option<StateId>,
+    current: StateId,
+    saved: StateId, // State matching the file on disk
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root_redo_child: None,
+            current: ROOT,
+            saved: ROOT,
+        }
+    }
+
+    pub fn current(&self) -> StateId {
+        self.current
+    }
+
+    /// Id of the newest state in the tree.
+    pub fn latest(&self) -> StateId {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, id: StateId) -> &UndoNode {
+        &self.nodes[id - 1]
+    }
+
+    /// True if the current state differs from what's on disk.
+    pub fn is_modified(&self) -> bool {
+        self.saved != self.current
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current;
+    }
+
+    pub fn saved(&self) -> StateId {
+        self.saved
+    }
+
+    /// Add a change on top of the current state and move to it.
+    pub fn push(&mut self, change: UndoState) {
+        self.nodes.push(UndoNode {
+            change,
+            parent: self.current,
+            redo_child: None,
+        });
+        let id = self.nodes.len();
+        *self.redo_slot(self.current) = Some(id);
+        self.current = id;
+    }
+
+    /// Step back to the parent state. Returns the change that has to be reverted.
+    pub fn undo(&mut self) -> Option<&UndoState> {
+        if self.current == ROOT {
+            return None;
+        }
+        let id = self.current;
+        let parent = self.node(id).parent;
+        *self.redo_slot(parent) = Some(id);
+        self.current = parent;
+        Some(&self.node(id).change)
+    }
+
+    /// Step forward along the most recently used branch. Returns the change that has to be reapplied.
+    pub fn redo(&mut self) -> Option<&UndoState> {
+        let child = (*self.redo_slot(self.current))?;
+        self.current = child;
+        Some(&self.node(child).change)
+    }
+
+    /// Move forward into a specific child of the current state, making it the branch redo follows.
+    pub fn redo_into(&mut self, child: StateId) -> &UndoState {
+        debug_assert_eq!(self.node(child).parent, self.current);
+        *self.redo_slot(self.current) = Some(child);
+        self.current = child;
+        &self.node(child).change
+    }
+
+    /// Route between two states: the nodes to undo on the way up from `from` (nearest first),
+    /// then the nodes to redo on the way down to `to` (in order).
+    pub fn path(&self, from: StateId, to: StateId) -> (Vec<StateId>, Vec<StateId>) {
+        let up = self.ancestors(from);
+        let down = self.ancestors(to);
+        // Both lists end at ROOT, so strip the shared tail to find where the branches meet
+        let shared = up.iter().rev().zip(down.iter().rev()).take_while(|(a, b)| a == b).count();
+        let undo_path = up[..up.len() - shared].to_vec();
+        let redo_path = down[..down.len() - shared].iter().rev().copied().collect();
+        (undo_path, redo_path)
+    }
+
+    /// Serialize the whole tree, recording `at` as both the current and the saved state.
+    pub fn write_to<W: Write>(&self, w: &mut W, at: StateId) -> io::Result<()> {
+        write_u64(w, self.nodes.len() as u64)?;
+        write_u64(w, at as u64)?;
+        write_u64(w, encode_child(self.root_redo_child))?;
+        for node in &self.nodes {
+            write_u64(w, node.parent as u64)?;
+            write_u64(w, encode_child(node.redo_child))?;
+            write_change(w, &node.change)?;
+        }
+        Ok(())
+    }
+
+    /// Read a tree written by `write_to`, rejecting any that isn't well formed.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "corrupt undo history");
+        let count = read_u64(r)? as usize;
+        let at = read_u64(r)? as usize;
+        let root_redo_child = decode_child(read_u64(r)?);
+        if at > count {
+            return Err(invalid());
+        }
+        let mut nodes = Vec::new();
+        for id in 1..=count {
+            let parent = read_u64(r)? as usize;
+            let redo_child = decode_child(read_u64(r)?);
+            // Children are always created after their parent
+            if parent >= id {
+                return Err(invalid());
+            }
+            nodes.push(UndoNode { change: read_change(r)?, parent, redo_child });
+        }
+        let tree = Self {
+            nodes,
+            root_redo_child,
+            current: at,
+            saved: at,
+        };
+        // Redo must always lead to one of the state's own children
+        let redo_ok = |id: StateId, child: Option<StateId>| {
+            child.is_none_or(|c| c > id && c <= count && tree.node(c).parent == id)
+        };
+        if !redo_ok(ROOT, tree.root_redo_child) || !(1..=count).all(|id| redo_ok(id, tree.node(id).redo_child)) {
+            return Err(invalid());
+        }
+        Ok(tree)
+    }
+
+    /// `id` followed by each of its ancestors, ending with ROOT.
+    fn ancestors(&self, mut id: StateId) -> Vec<StateId> {
+        let mut chain = vec![id];
+        while id != ROOT {
+            id = self.node(id).parent;
+            chain.push(id);
+        }
+        chain
+    }
+
+    fn redo_slot(&mut self, id: StateId) -> &mut Option<StateId> {
+        if id == ROOT {
+            &mut self.root_redo_child
+        } else {
+            &mut self.nodes[id - 1].redo_child
+        }
+    }
+}
+
+/// Optional child ids are stored as id + 1, with 0 meaning "none".
+fn encode_child(child: Option<StateId>) -> u64 {
+    child.map_or(0, |id| id as u64 + 1)
+}
+
+fn decode_child(value: u64) -> Option<StateId> {
+    value.checked_sub(1).map(|id| id as StateId)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::CursorState;
+
+    fn change(pos: usize) -> UndoState {
+        let cursor = CursorState { cursor_pos: pos, offset: 0, pending_digits: 0 };
+        UndoState { pos, old_bytes: vec![pos as u8], new_bytes: vec![0xff], before: cursor, after: cursor }
+    }
+
+    /// ROOT -> 1 -> 2, 1 -> 3 and ROOT -> 4, left at state 4.
+    fn branching_tree() -> UndoTree {
+        let mut tree = UndoTree::new();
+        tree.push(change(1));
+        tree.push(change(2));
+        tree.undo();
+        tree.push(change(3));
+        tree.undo();
+        tree.undo();
+        tree.push(change(4));
+        tree
+    }
+
+    #[test]
+    fn new_edits_start_branches_instead_of_discarding() {
+        let tree = branching_tree();
+        assert_eq!(tree.latest(), 4);
+        assert_eq!(tree.current(), 4);
+        let parents: Vec<StateId> = (1..=4).map(|id| tree.node(id).parent).collect();
+        assert_eq!(parents, [ROOT, 1, 1, ROOT]);
+    }
+
+    #[test]
+    fn redo_follows_the_most_recent_branch() {
+        let mut tree = branching_tree();
+        assert!(tree.redo().is_none());
+        assert_eq!(tree.undo().map(|c| c.pos), Some(4));
+        assert_eq!(tree.current(), ROOT);
+        assert!(tree.undo().is_none());
+        assert_eq!(tree.redo().map(|c| c.pos), Some(4));
+
+        tree.undo();
+        assert_eq!(tree.redo_into(1).pos, 1);
+        // State 3 was visited more recently than state 2
+        assert_eq!(tree.redo().map(|c| c.pos), Some(3));
+        tree.undo();
+        tree.redo_into(2);
+        tree.undo();
+        assert_eq!(tree.redo().map(|c| c.pos), Some(2));
+    }
+
+    #[test]
+    fn paths_go_through_the_common_ancestor() {
+        let tree = branching_tree();
+        assert_eq!(tree.path(2, 3), (vec![2], vec![3]));
+        assert_eq!(tree.path(2, 4), (vec![2, 1], vec![4]));
+        assert_eq!(tree.path(ROOT, 3), (vec![], vec![1, 3]));
+        assert_eq!(tree.path(3, ROOT), (vec![3, 1], vec![]));
+        assert_eq!(tree.path(1, 2), (vec![], vec![2]));
+        assert_eq!(tree.path(3, 3), (vec![], vec![]));
+    }
+
+    #[test]
+    fn saving_moves_the_unmodified_state() {
+        let mut tree = branching_tree();
+        assert!(tree.is_modified());
+        tree.mark_saved();
+        assert!(!tree.is_modified());
+        assert_eq!(tree.saved(), 4);
+        tree.undo();
+        assert!(tree.is_modified());
+        tree.redo();
+        assert!(!tree.is_modified());
+    }
+
+    fn written(tree: &UndoTree, at: StateId) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        tree.write_to(&mut bytes, at).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn trees_read_back_as_written() {
+        let tree = branching_tree();
+        let read = UndoTree::read_from(&mut &written(&tree, 2)[..]).unwrap();
+        assert_eq!((read.latest(), read.current(), read.saved()), (4, 2, 2));
+        for id in 1..=4 {
+            let (node, original) = (read.node(id), tree.node(id));
+            assert_eq!(node.parent, original.parent);
+            assert_eq!(node.change.pos, original.change.pos);
+            assert_eq!(node.change.old_bytes, original.change.old_bytes);
+            assert_eq!(node.change.new_bytes, original.change.new_bytes);
+            assert_eq!(node.change.after.cursor_pos, original.change.after.cursor_pos);
+        }
+        // The branch redo follows is kept too
+        let mut at_root = UndoTree::read_from(&mut &written(&tree, ROOT)[..]).unwrap();
+        assert_eq!(at_root.redo().map(|c| c.pos), Some(4));
+        let mut at_first = UndoTree::read_from(&mut &written(&tree, 1)[..]).unwrap();
+        assert_eq!(at_first.redo().map(|c| c.pos), Some(3));
+    }
+
+    #[test]
+    fn corrupt_trees_are_rejected() {
+        let bytes = written(&branching_tree(), 4);
+        let corrupted = |offset: usize, value: u64| {
+            let mut bytes = bytes.clone();
+            bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            UndoTree::read_from(&mut &bytes[..]).is_err()
+        };
+        assert!(corrupted(8, 5), "current state past the last node");
+        assert!(corrupted(16, encode_child(Some(2))), "redo into a grandchild");
+        assert!(corrupted(24, 1), "a node that is its own parent");
+        assert!(UndoTree::read_from(&mut &bytes[..]).is_ok());
+        for len in 0..bytes.len() {
+            assert!(UndoTree::read_from(&mut &bytes[..len]).is_err(), "truncated to {len} bytes");
+        }
+    }
+}
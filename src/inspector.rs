@@ -0,0 +1,547 @@
+//! Data inspector.
+//!
+//! Decodes the bytes at the cursor as the common fixed-size and variable-length types found in binary
+//! formats, and encodes a typed-in value back into bytes. Every field reads from the cursor onwards; fields
+//! that need more bytes than are left in the file show nothing.
+
+use crate::config::Endian;
+
+/// Seconds between 1601-01-01 (the FILETIME epoch) and 1970-01-01.
+const FILETIME_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// FILETIME ticks (100 ns) per second.
+const FILETIME_TICKS: u64 = 10_000_000;
+
+/// Most bytes any field reads.
+pub const MAX_FIELD_LEN: usize = 16;
+
+#[derive(Clone, Copy)]
+pub enum Field {
+    Bits,                                           // The byte at the cursor, bit by bit
+    Int { size: usize, signed: bool, endian: Endian },
+    Float { size: usize, endian: Endian },          // IEEE 754 half, single or double precision
+    Uleb128,
+    Sleb128,
+    Unix { size: usize, endian: Endian },           // Signed seconds since 1970-01-01 UTC
+    FileTime,                                       // Little-endian 100 ns ticks since 1601-01-01 UTC
+    DosDateTime,                                    // Little-endian FAT/ZIP time word followed by date word
+    Guid,                                           // Windows layout: first three groups little-endian
+}
+
+/// Every field, in the order the panel lists them.
+pub fn fields() -> Vec<Field> {
+    let mut fields = vec![
+        Field::Bits,
+        Field::Int { size: 1, signed: false, endian: Endian::Little },
+        Field::Int { size: 1, signed: true, endian: Endian::Little },
+    ];
+    for size in [2, 4, 8] {
+        for signed in [false, true] {
+            for endian in [Endian::Little, Endian::Big] {
+                fields.push(Field::Int { size, signed, endian });
+            }
+        }
+    }
+    for size in [2, 4, 8] {
+        for endian in [Endian::Little, Endian::Big] {
+            fields.push(Field::Float { size, endian });
+        }
+    }
+    fields.extend([Field::Uleb128, Field::Sleb128]);
+    for size in [4, 8] {
+        for endian in [Endian::Little, Endian::Big] {
+            fields.push(Field::Unix { size, endian });
+        }
+    }
+    fields.extend([Field::FileTime, Field::DosDateTime, Field::Guid]);
+    fields
+}
+
+impl Field {
+    /// Short name for the panel, such as `u32 LE`.
+    pub fn name(self) -> String {
+        let endian = |endian| if endian == Endian::Little { "LE" } else { "BE" };
+        match self {
+            Field::Bits => String::from("binary"),
+            Field::Int { size: 1, signed, .. } => String::from(if signed { "i8" } else { "u8" }),
+            Field::Int { size, signed, endian: e } => {
+                format!("{}{} {}", if signed { 'i' } else { 'u' }, size * 8, endian(e))
+            }
+            Field::Float { size, endian: e } => format!("f{} {}", size * 8, endian(e)),
+            Field::Uleb128 => String::from("ULEB128"),
+            Field::Sleb128 => String::from("SLEB128"),
+            Field::Unix { size, endian: e } => format!("unix{} {}", size * 8, endian(e)),
+            Field::FileTime => String::from("FILETIME"),
+            Field::DosDateTime => String::from("DOS time"),
+            Field::Guid => String::from("GUID"),
+        }
+    }
+
+    /// The value of `bytes` (read from the cursor onwards) as this field, or None if there aren't enough bytes.
+    pub fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Field::Bits => bytes.first().map(|b| format!("{b:08b}")),
+            Field::Int { size, signed, endian } => {
+                let value = read_uint(bytes, size, endian)?;
+                Some(if signed { sign_extend(value, size).to_string() } else { value.to_string() })
+            }
+            Field::Float { size, endian } => {
+                let bits = read_uint(bytes, size, endian)? as u64;
+                let value = match size {
+                    2 => f16_to_f64(bits as u16),
+                    4 => f32::from_bits(bits as u32) as f64,
+                    _ => f64::from_bits(bits),
+                };
+                Some(format_float(value, size))
+            }
+            Field::Uleb128 => {
+                let (value, len) = read_leb128(bytes)?;
+                Some(format!("{value} ({})", byte_count(len)))
+            }
+            Field::Sleb128 => {
+                let (value, len) = read_leb128(bytes)?;
+                let shift = 128 - 7 * len as u32;
+                let value = ((value as i128) << shift) >> shift;
+                Some(format!("{value} ({})", byte_count(len)))
+            }
+            Field::Unix { size, endian } => {
+                let secs = sign_extend(read_uint(bytes, size, endian)?, size) as i64;
+                Some(format_datetime(secs).unwrap_or_else(|| String::from("out of range")))
+            }
+            Field::FileTime => {
+                let ticks = read_uint(bytes, 8, Endian::Little)? as u64;
+                let secs = (ticks / FILETIME_TICKS) as i64 - FILETIME_EPOCH_OFFSET;
+                Some(match format_datetime(secs) {
+                    Some(date) => format!("{date}.{:07}", ticks % FILETIME_TICKS),
+                    None => String::from("out of range"),
+                })
+            }
+            Field::DosDateTime => {
+                let time = read_uint(bytes, 2, Endian::Little)? as u32;
+                let date = read_uint(bytes.get(2..)?, 2, Endian::Little)? as u32;
+                let (year, month, day) = (1980 + (date >> 9) as i64, (date >> 5) & 0xf, date & 0x1f);
+                let (hour, minute, second) = (time >> 11, (time >> 5) & 0x3f, (time & 0x1f) * 2);
+                if !is_valid_date(year, month, day) || hour > 23 || minute > 59 || second > 59 {
+                    return Some(String::from("invalid"));
+                }
+                Some(format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}"))
+            }
+            Field::Guid => {
+                let b = bytes.get(..16)?;
+                let data1 = read_uint(b, 4, Endian::Little)?;
+                let data2 = read_uint(&b[4..], 2, Endian::Little)?;
+                let data3 = read_uint(&b[6..], 2, Endian::Little)?;
+                let tail: String = b[10..].iter().map(|b| format!("{b:02x}")).collect();
+                Some(format!("{data1:08x}-{data2:04x}-{data3:04x}-{:02x}{:02x}-{tail}", b[8], b[9]))
+            }
+        }
+    }
+
+    /// Bytes that store `input` as this field. Variable-length fields take as many bytes as they need.
+    pub fn encode(self, input: &str) -> Result<Vec<u8>, String> {
+        let input = input.trim();
+        match self {
+            Field::Bits => {
+                if input.is_empty() || input.len() > 8 {
+                    return Err(String::from("Expected up to 8 binary digits"));
+                }
+                u8::from_str_radix(input, 2).map(|b| vec![b]).map_err(|_| format!("'{input}' is not binary"))
+            }
+            Field::Int { size, signed, endian } => {
+                let value = parse_integer(input)?;
+                let bits = size as u32 * 8;
+                let (min, max) = if signed {
+                    (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+                } else {
+                    (0, (1i128 << bits) - 1)
+                };
+                if value < min || value > max {
+                    return Err(format!("{} must be between {min} and {max}", self.name()));
+                }
+                Ok(write_uint(value as u128, size, endian))
+            }
+            Field::Float { size, endian } => {
+                let value: f64 = input.parse().map_err(|_| format!("'{input}' is not a number"))?;
+                let bits = match size {
+                    2 => f64_to_f16(value) as u128,
+                    4 => (value as f32).to_bits() as u128,
+                    _ => value.to_bits() as u128,
+                };
+                Ok(write_uint(bits, size, endian))
+            }
+            Field::Uleb128 => {
+                let value = parse_integer(input)?;
+                if !(0..=u64::MAX as i128).contains(&value) {
+                    return Err(String::from("ULEB128 values must be between 0 and 2^64-1"));
+                }
+                Ok(write_leb128(value, false))
+            }
+            Field::Sleb128 => {
+                let value = parse_integer(input)?;
+                if !(i64::MIN as i128..=i64::MAX as i128).contains(&value) {
+                    return Err(String::from("SLEB128 values must fit in 64 bits"));
+                }
+                Ok(write_leb128(value, true))
+            }
+            Field::Unix { size, endian } => {
+                let secs = parse_datetime(input)?;
+                let bits = size as u32 * 8;
+                if secs < -(1i128 << (bits - 1)) || secs >= 1i128 << (bits - 1) {
+                    return Err(format!("{} can't hold that time", self.name()));
+                }
+                Ok(write_uint(secs as u128, size, endian))
+            }
+            Field::FileTime => {
+                let out_of_range = || String::from("FILETIME can't hold that time");
+                let ticks = parse_datetime(input)?
+                    .checked_add(FILETIME_EPOCH_OFFSET as i128)
+                    .and_then(|secs| secs.checked_mul(FILETIME_TICKS as i128))
+                    .ok_or_else(out_of_range)?;
+                if !(0..=u64::MAX as i128).contains(&ticks) {
+                    return Err(out_of_range());
+                }
+                Ok(write_uint(ticks as u128, 8, Endian::Little))
+            }
+            Field::DosDateTime => {
+                let secs = i64::try_from(parse_datetime(input)?).map_err(|_| String::from("Time out of range"))?;
+                let days = secs.div_euclid(86_400);
+                let (year, month, day) = civil_from_days(days);
+                if !(1980..=2107).contains(&year) {
+                    return Err(String::from("DOS dates must be between 1980 and 2107"));
+                }
+                let time_of_day = secs.rem_euclid(86_400) as u32;
+                let time = ((time_of_day / 3600) << 11) | ((time_of_day / 60 % 60) << 5) | (time_of_day % 60 / 2);
+                let date = (((year - 1980) as u32) << 9) | (month << 5) | day;
+                let mut bytes = write_uint(time as u128, 2, Endian::Little);
+                bytes.extend(write_uint(date as u128, 2, Endian::Little));
+                Ok(bytes)
+            }
+            Field::Guid => {
+                let digits: String = input.chars().filter(|c| !matches!(c, '-' | '{' | '}')).collect();
+                if digits.len() != 32 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(String::from("Expected a GUID like 00112233-4455-6677-8899-aabbccddeeff"));
+                }
+                let mut bytes: Vec<u8> =
+                    (0..16).map(|i| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).unwrap()).collect();
+                bytes[..4].reverse();
+                bytes[4..6].reverse();
+                bytes[6..8].reverse();
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+fn byte_count(len: usize) -> String {
+    if len == 1 { String::from("1 byte") } else { format!("{len} bytes") }
+}
+
+/// The first `size` bytes as an unsigned number, or None if there are fewer.
+fn read_uint(bytes: &[u8], size: usize, endian: Endian) -> Option<u128> {
+    let bytes = bytes.get(..size)?;
+    let fold = |value: u128, b: &u8| (value << 8) | *b as u128;
+    Some(match endian {
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+        Endian::Big => bytes.iter().fold(0, fold),
+    })
+}
+
+/// The low `size` bytes of `value` in the given byte order.
+fn write_uint(value: u128, size: usize, endian: Endian) -> Vec<u8> {
+    let mut bytes = value.to_le_bytes()[..size].to_vec();
+    if endian == Endian::Big {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// Reinterpret a `size`-byte unsigned value as two's complement.
+fn sign_extend(value: u128, size: usize) -> i128 {
+    let shift = 128 - size as u32 * 8;
+    ((value << shift) as i128) >> shift
+}
+
+/// A LEB128 value and how many bytes it took, or None if it doesn't end within `bytes` or 10 bytes.
+fn read_leb128(bytes: &[u8]) -> Option<(u128, usize)> {
+    let mut value = 0u128;
+    for (i, b) in bytes.iter().take(10).enumerate() {
+        value |= ((b & 0x7f) as u128) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn write_leb128(mut value: i128, signed: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let b = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = if signed {
+            (value == 0 && b & 0x40 == 0) || (value == -1 && b & 0x40 != 0)
+        } else {
+            value == 0
+        };
+        if done {
+            bytes.push(b);
+            return bytes;
+        }
+        bytes.push(b | 0x80);
+    }
+}
+
+/// A decimal integer, or hex/octal/binary with a `0x`/`0o`/`0b` prefix, optionally negative.
+fn parse_integer(input: &str) -> Result<i128, String> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, input),
+    };
+    let lower = digits.to_ascii_lowercase();
+    let (radix, digits) = match lower.get(..2) {
+        Some("0x") => (16, &lower[2..]),
+        Some("0o") => (8, &lower[2..]),
+        Some("0b") => (2, &lower[2..]),
+        _ => (10, &lower[..]),
+    };
+    let digits = digits.replace('_', "");
+    let error = || format!("'{input}' is not a valid number");
+    // from_str_radix takes a sign of its own, which would let `--5` through
+    if digits.starts_with(['+', '-']) {
+        return Err(error());
+    }
+    let value = i128::from_str_radix(&digits, radix).map_err(|_| error())?;
+    Ok(if negative { -value } else { value })
+}
+
+/// Floats with a sensible number of digits for their precision, in exponent form when very large or small.
+fn format_float(value: f64, size: usize) -> String {
+    let abs = value.abs();
+    let exponent_form = abs != 0.0 && value.is_finite() && !(1e-5..1e15).contains(&abs);
+    match (size, exponent_form) {
+        (8, true) => format!("{value:e}"),
+        (8, false) => value.to_string(),
+        (_, true) => format!("{:e}", value as f32),
+        (_, false) => (value as f32).to_string(),
+    }
+}
+
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let fraction = (bits & 0x3ff) as f64;
+    match exponent {
+        0 => sign * fraction * 2f64.powi(-24),
+        0x1f if fraction == 0.0 => sign * f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => sign * (1.0 + fraction / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+/// Nearest half-precision value, rounding ties to even. Anything too large becomes infinity.
+fn f64_to_f16(value: f64) -> u16 {
+    let sign = if value.is_sign_negative() { 0x8000 } else { 0 };
+    let abs = value.abs();
+    if value.is_nan() {
+        return 0x7e00;
+    }
+    if abs >= 65520.0 {
+        return sign | 0x7c00;
+    }
+    if abs < 2f64.powi(-14) {
+        // Subnormal; rounding up to 0x400 lands exactly on the smallest normal number
+        return sign | (abs * 2f64.powi(24)).round_ties_even() as u16;
+    }
+    let mut exponent = abs.log2().floor() as i32;
+    if abs / 2f64.powi(exponent) >= 2.0 {
+        exponent += 1;
+    }
+    let mantissa = ((abs / 2f64.powi(exponent) - 1.0) * 1024.0).round_ties_even() as u16;
+    // A mantissa that rounded up to 1024 carries into the exponent
+    sign | ((((exponent + 15) as u16) << 10) + mantissa)
+}
+
+/// Seconds since 1970-01-01 as `YYYY-MM-DD HH:MM:SS` UTC, or None outside years 0 to 9999.
+fn format_datetime(secs: i64) -> Option<String> {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+    let time = secs.rem_euclid(86_400);
+    Some(format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    ))
+}
+
+/// `YYYY-MM-DD`, optionally followed by ` HH:MM:SS` (or `T` instead of the space), as seconds since
+/// 1970-01-01 UTC. A plain integer is taken as the number of seconds itself.
+fn parse_datetime(input: &str) -> Result<i128, String> {
+    if let Ok(secs) = parse_integer(input) {
+        return Ok(secs);
+    }
+    let error = || format!("'{input}' is not a date like 2024-01-31 12:00:00");
+    let (date, time) = input.split_once([' ', 'T']).unwrap_or((input, "00:00:00"));
+    let parts = |text: &str, separator: char| -> Result<Vec<u32>, String> {
+        text.split(separator).map(|n| n.trim().parse::<u32>().map_err(|_| error())).collect()
+    };
+    let [year, month, day] = parts(date, '-')?[..] else { return Err(error()) };
+    let [hour, minute, second] = parts(time, ':')?[..] else { return Err(error()) };
+    if !is_valid_date(year as i64, month, day) || hour > 23 || minute > 59 || second > 59 {
+        return Err(error());
+    }
+    let days = days_from_civil(year as i64, month, day) as i128;
+    Ok(days * 86_400 + (hour * 3600 + minute * 60 + second) as i128)
+}
+
+fn is_valid_date(year: i64, month: u32, day: u32) -> bool {
+    (1..=12).contains(&month) && day >= 1 && civil_from_days(days_from_civil(year, month, day)) == (year, month, day)
+}
+
+/// Days since 1970-01-01 as a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm, counting in 400-year eras starting on 0000-03-01
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str) -> Field {
+        fields().into_iter().find(|field| field.name() == name).unwrap()
+    }
+
+    /// Encode `input` as the named field and decode the bytes straight back.
+    fn round_trip(name: &str, input: &str) -> (Vec<u8>, String) {
+        let bytes = field(name).encode(input).unwrap();
+        let decoded = field(name).decode(&bytes).unwrap();
+        (bytes, decoded)
+    }
+
+    #[test]
+    fn integers_round_trip_in_both_byte_orders() {
+        assert_eq!(round_trip("u16 LE", "0x1234"), (vec![0x34, 0x12], String::from("4660")));
+        assert_eq!(round_trip("u16 BE", "0x1234"), (vec![0x12, 0x34], String::from("4660")));
+        assert_eq!(round_trip("i32 BE", "-1"), (vec![0xff; 4], String::from("-1")));
+        assert_eq!(round_trip("i8", "-128").1, "-128");
+        assert_eq!(round_trip("u64 LE", "18446744073709551615").1, "18446744073709551615");
+        assert_eq!(round_trip("u32 LE", "0b1010_1010").0, [0xaa, 0, 0, 0]);
+    }
+
+    #[test]
+    fn integers_out_of_range_are_rejected() {
+        assert!(field("u8").encode("256").is_err());
+        assert!(field("u8").encode("-1").is_err());
+        assert!(field("i8").encode("-129").is_err());
+        assert!(field("i16 LE").encode("32768").is_err());
+        assert!(field("u64 LE").encode("18446744073709551616").is_err());
+        assert!(field("u32 LE").encode("--5").is_err());
+        assert!(field("u32 LE").encode("twelve").is_err());
+    }
+
+    #[test]
+    fn floats_round_trip() {
+        assert_eq!(round_trip("f16 LE", "1"), (vec![0x00, 0x3c], String::from("1")));
+        assert_eq!(round_trip("f16 BE", "-2.5").1, "-2.5");
+        assert_eq!(round_trip("f16 LE", "65504").1, "65504");
+        assert_eq!(round_trip("f32 LE", "1.5"), (vec![0, 0, 0xc0, 0x3f], String::from("1.5")));
+        assert_eq!(round_trip("f32 BE", "0.1").1, "0.1");
+        assert_eq!(round_trip("f64 LE", "-2.25").1, "-2.25");
+        assert!(field("f32 LE").encode("one").is_err());
+    }
+
+    #[test]
+    fn leb128_round_trips() {
+        assert_eq!(round_trip("ULEB128", "624485"), (vec![0xe5, 0x8e, 0x26], String::from("624485 (3 bytes)")));
+        assert_eq!(round_trip("ULEB128", "0"), (vec![0], String::from("0 (1 byte)")));
+        assert_eq!(round_trip("SLEB128", "-123456"), (vec![0xc0, 0xbb, 0x78], String::from("-123456 (3 bytes)")));
+        assert_eq!(round_trip("SLEB128", "63").1, "63 (1 byte)");
+        assert_eq!(round_trip("SLEB128", "64").1, "64 (2 bytes)");
+        assert!(field("ULEB128").encode("-1").is_err());
+        assert!(field("SLEB128").encode("9223372036854775808").is_err());
+        // A value whose last byte never comes isn't decoded
+        assert_eq!(field("ULEB128").decode(&[0x80, 0x80]), None);
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        assert_eq!(round_trip("unix32 LE", "2024-01-31 12:00:00").1, "2024-01-31 12:00:00");
+        assert_eq!(round_trip("unix64 BE", "1969-07-20T20:17:40").1, "1969-07-20 20:17:40");
+        assert_eq!(round_trip("unix32 LE", "0").1, "1970-01-01 00:00:00");
+        let (bytes, decoded) = round_trip("FILETIME", "1970-01-01");
+        assert_eq!(bytes, 116_444_736_000_000_000u64.to_le_bytes());
+        assert_eq!(decoded, "1970-01-01 00:00:00.0000000");
+        assert_eq!(round_trip("FILETIME", "1601-01-01").0, [0; 8]);
+        // DOS times only have two-second resolution
+        assert_eq!(round_trip("DOS time", "2024-01-31 12:34:57").1, "2024-01-31 12:34:56");
+    }
+
+    #[test]
+    fn timestamps_out_of_range_are_rejected() {
+        assert!(field("unix32 LE").encode("2038-01-19 03:14:08").is_err());
+        assert!(field("unix32 LE").encode("2038-01-19 03:14:07").is_ok());
+        assert!(field("FILETIME").encode("1600-12-31 23:59:59").is_err());
+        assert!(field("FILETIME").encode("100000000000000000000000000000000").is_err());
+        assert!(field("FILETIME").encode("-100000000000000000000000000000000").is_err());
+        assert!(field("DOS time").encode("1979-12-31 23:59:59").is_err());
+        assert!(field("DOS time").encode("2108-01-01").is_err());
+        assert!(field("unix64 LE").encode("2023-02-29").is_err());
+        assert!(field("unix64 LE").encode("2024-01-31 24:00:00").is_err());
+        assert!(field("unix64 LE").encode("yesterday").is_err());
+    }
+
+    #[test]
+    fn guid_round_trips() {
+        let (bytes, decoded) = round_trip("GUID", "{00112233-4455-6677-8899-AABBCCDDEEFF}");
+        // The first three groups are stored little-endian
+        assert_eq!(bytes[..8], [0x33, 0x22, 0x11, 0x00, 0x55, 0x44, 0x77, 0x66]);
+        assert_eq!(bytes[8..], [0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        assert_eq!(decoded, "00112233-4455-6677-8899-aabbccddeeff");
+        assert!(field("GUID").encode("00112233-4455").is_err());
+    }
+
+    #[test]
+    fn fields_need_enough_bytes() {
+        assert_eq!(field("u32 LE").decode(&[1, 2, 3]), None);
+        assert_eq!(field("GUID").decode(&[0; 15]), None);
+        assert_eq!(field("binary").decode(&[]), None);
+        assert_eq!(field("binary").decode(&[5]).unwrap(), "00000101");
+    }
+
+    #[test]
+    fn decoded_values_encode_back_to_the_same_bytes() {
+        let bytes = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x02];
+        for field in fields() {
+            let size = match field {
+                Field::Int { size, .. } | Field::Float { size, .. } => size,
+                Field::Guid => 16,
+                _ => continue,
+            };
+            let value = field.decode(&bytes).unwrap();
+            assert_eq!(field.encode(&value).unwrap(), bytes[..size], "{} {value}", field.name());
+        }
+    }
+}
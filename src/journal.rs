@@ -0,0 +1,447 @@
+//! On-disk journals kept next to the edited file.
+//!
+//! Provides the small binary encoding used by sidecar files, content hashing to tell whether the file they
+//! belong to has changed, the persistent undo history (`.<name>.microhex-undo`), the crash-recovery
+//! swap file (`.<name>.microhex-swp`) and saved bookmarks (`.<name>.microhex-marks`).
+//! All integers are stored as little-endian u64 so the format doesn't depend on the platform.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::edit;
+use crate::editor::{CursorState, MicroHex, UndoState};
+use crate::history::UndoTree;
+use crate::save::FileStamp;
+
+// Version 2 stores the number of digits typed into the cursor's cell, where version 1 stored a pending
+// nibble's value + 1, so version 1 files are rejected rather than misread. Swap version 3 also records the
+// file's inode along with its size and modification time
+const UNDO_MAGIC: &[u8; 8] = b"MHUNDO2\n";
+const SWAP_MAGIC: &[u8; 8] = b"MHSWAP3\n";
+const MARKS_MAGIC: &[u8; 8] = b"MHMARK1\n";
+const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Path of the sidecar file `.<name>.<suffix>` next to `path`.
+pub fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{name}.{suffix}"))
+}
+
+/// 64-bit FNV-1a hash of everything `reader` produces.
+pub fn hash_reader<R: Read>(mut reader: R) -> io::Result<u64> {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut chunk = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(hash);
+        }
+        for &byte in &chunk[..n] {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// Size and content hash of the file at `path`, used to check a journal still belongs to it.
+pub fn file_fingerprint(path: &Path) -> io::Result<(u64, u64)> {
+    let len = fs::metadata(path)?.len();
+    let hash = hash_reader(File::open(path)?)?;
+    Ok((len, hash))
+}
+
+pub fn write_u64<W: Write>(w: &mut W, value: u64) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn write_bytes<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u64(w, bytes.len() as u64)?;
+    w.write_all(bytes)
+}
+
+pub fn read_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u64(r)?;
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated journal"));
+    }
+    Ok(bytes)
+}
+
+fn write_cursor<W: Write>(w: &mut W, cursor: &CursorState) -> io::Result<()> {
+    write_u64(w, cursor.cursor_pos as u64)?;
+    write_u64(w, cursor.offset as u64)?;
+    write_u64(w, cursor.pending_digits as u64)
+}
+
+fn read_cursor<R: Read>(r: &mut R) -> io::Result<CursorState> {
+    Ok(CursorState {
+        cursor_pos: read_u64(r)? as usize,
+        offset: read_u64(r)? as usize,
+        pending_digits: read_u64(r)?.min(u8::MAX as u64) as u8,
+    })
+}
+
+pub fn write_change<W: Write>(w: &mut W, change: &UndoState) -> io::Result<()> {
+    write_u64(w, change.pos as u64)?;
+    write_bytes(w, &change.old_bytes)?;
+    write_bytes(w, &change.new_bytes)?;
+    write_cursor(w, &change.before)?;
+    write_cursor(w, &change.after)
+}
+
+pub fn read_change<R: Read>(r: &mut R) -> io::Result<UndoState> {
+    Ok(UndoState {
+        pos: read_u64(r)? as usize,
+        old_bytes: read_bytes(r)?,
+        new_bytes: read_bytes(r)?,
+        before: read_cursor(r)?,
+        after: read_cursor(r)?,
+    })
+}
+
+/// Write the editor's undo history to its sidecar file, tagged with the size and hash of the file on disk.
+/// The history is stored from the saved state's point of view, since that's what the file will contain
+/// next time it's opened. An empty history removes any old sidecar instead.
+pub fn save_undo_history(editor: &MicroHex) -> io::Result<()> {
+    let path = Path::new(&editor.filename);
+    let journal_path = sidecar_path(path, "microhex-undo");
+    if editor.history.latest() == 0 {
+        return match fs::remove_file(&journal_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    let (len, hash) = file_fingerprint(path)?;
+    let mut w = BufWriter::new(File::create(&journal_path)?);
+    w.write_all(UNDO_MAGIC)?;
+    write_u64(&mut w, len)?;
+    write_u64(&mut w, hash)?;
+    editor.history.write_to(&mut w, editor.history.saved())?;
+    w.flush()
+}
+
+/// Restore undo history from the file's sidecar, if there is one and the file hasn't changed since it was
+/// written. A journal for a different version of the file is deleted. Returns true if history was restored.
+pub fn load_undo_history(editor: &mut MicroHex) -> io::Result<bool> {
+    let path = Path::new(&editor.filename);
+    let journal_path = sidecar_path(path, "microhex-undo");
+    let file = match File::open(&journal_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let mut r = BufReader::new(file);
+
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    let len = read_u64(&mut r)?;
+    let hash = read_u64(&mut r)?;
+    // Check the cheap size first so we only hash the file when it could still match
+    let matches = magic == *UNDO_MAGIC
+        && len == fs::metadata(path)?.len()
+        && file_fingerprint(path)? == (len, hash);
+    if !matches {
+        fs::remove_file(&journal_path)?;
+        return Ok(false);
+    }
+
+    editor.history = UndoTree::read_from(&mut r)?;
+    // Start where the file on disk is; redo leads back to where the last session left off
+    let saved = editor.history.saved();
+    let cursor = if saved == 0 { CursorState::of(editor) } else { editor.history.node(saved).change.after };
+    cursor.restore(editor);
+    Ok(true)
+}
+
+/// Unsaved edits left behind in a swap file.
+pub struct Swap {
+    pub stamp: FileStamp, // The file on disk when the swap was written
+    pub changes: Vec<UndoState>, // Applied in order, they turn the file on disk into the unsaved buffer
+}
+
+impl Swap {
+    /// True if the file is still the one the swap was written for. If not, the recorded edits may no longer
+    /// line up with its contents.
+    pub fn matches_file(&self, path: &Path) -> bool {
+        FileStamp::of(path).is_ok_and(|stamp| stamp == self.stamp)
+    }
+}
+
+pub fn swap_path(path: &Path) -> PathBuf {
+    sidecar_path(path, "microhex-swp")
+}
+
+/// Write every unsaved change to the swap file. The new swap is written beside the old one and renamed
+/// over it, so a crash part way through never leaves a half-written swap behind.
+pub fn write_swap(editor: &MicroHex) -> io::Result<()> {
+    let path = Path::new(&editor.filename);
+    let swap_path = swap_path(path);
+    let tmp_path = sidecar_path(path, "microhex-swp-tmp");
+    let changes = edit::pending_changes(editor);
+    let stamp = FileStamp::of(path)?;
+
+    let mut w = BufWriter::new(File::create(&tmp_path)?);
+    w.write_all(SWAP_MAGIC)?;
+    for value in stamp.to_raw() {
+        write_u64(&mut w, value)?;
+    }
+    write_u64(&mut w, changes.len() as u64)?;
+    for change in &changes {
+        write_change(&mut w, change)?;
+    }
+    w.flush()?;
+    drop(w);
+    fs::rename(&tmp_path, &swap_path)
+}
+
+/// Read the swap file for `path`, if there is one.
+pub fn read_swap(path: &Path) -> io::Result<Option<Swap>> {
+    let file = match File::open(swap_path(path)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut r = BufReader::new(file);
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if magic != *SWAP_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a microhex swap file"));
+    }
+    let stamp = FileStamp::from_raw([read_u64(&mut r)?, read_u64(&mut r)?, read_u64(&mut r)?]);
+    let count = read_u64(&mut r)?;
+    let mut changes = Vec::new();
+    for _ in 0..count {
+        changes.push(read_change(&mut r)?);
+    }
+    Ok(Some(Swap { stamp, changes }))
+}
+
+/// Delete the swap file for `path`. A missing swap file is not an error.
+pub fn remove_swap(path: &Path) -> io::Result<()> {
+    match fs::remove_file(swap_path(path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Write the editor's marks and bookmarks to their sidecar file, or remove the sidecar if there are none.
+pub fn save_bookmarks(editor: &MicroHex) -> io::Result<()> {
+    let marks_path = sidecar_path(Path::new(&editor.filename), "microhex-marks");
+    if editor.bookmarks.is_empty() {
+        return match fs::remove_file(&marks_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    let mut w = BufWriter::new(File::create(&marks_path)?);
+    w.write_all(MARKS_MAGIC)?;
+    write_u64(&mut w, editor.bookmarks.list().len() as u64)?;
+    for bookmark in editor.bookmarks.list() {
+        write_bytes(&mut w, bookmark.name.as_bytes())?;
+        write_u64(&mut w, bookmark.pos as u64)?;
+        write_bytes(&mut w, bookmark.comment.as_bytes())?;
+    }
+    w.flush()
+}
+
+/// Restore marks and bookmarks from the file's sidecar, if there is one. Bookmarks are kept even if the
+/// file changed since they were saved; jumping past the end just lands on the last byte.
+pub fn load_bookmarks(editor: &mut MicroHex) -> io::Result<()> {
+    let marks_path = sidecar_path(Path::new(&editor.filename), "microhex-marks");
+    let file = match File::open(&marks_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut r = BufReader::new(file);
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if magic != *MARKS_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a microhex bookmarks file"));
+    }
+    let count = read_u64(&mut r)?;
+    let text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+    for _ in 0..count {
+        let name = text(read_bytes(&mut r)?);
+        let pos = read_u64(&mut r)? as usize;
+        let comment = text(read_bytes(&mut r)?);
+        editor.bookmarks.set(&name, pos, comment);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::CursorState;
+
+    /// An empty directory of its own for each test, so tests running in parallel don't share sidecar files.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("microhex-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn overwrite(editor: &mut MicroHex, pos: usize, new: &[u8]) {
+        let cursor = CursorState { cursor_pos: pos, offset: 0, pending_digits: 0 };
+        let old_bytes = editor.bytes.read_range(pos..pos + new.len());
+        let change = UndoState { pos, old_bytes, new_bytes: new.to_vec(), before: cursor, after: cursor };
+        edit::replay(editor, change);
+    }
+
+    fn contents(editor: &MicroHex) -> Vec<u8> {
+        editor.bytes.read_range(0..editor.bytes.len())
+    }
+
+    #[test]
+    fn undo_history_survives_a_restart() {
+        let dir = temp_dir("undo");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"abcdef").unwrap();
+        let name = path.to_string_lossy();
+
+        let mut editor = MicroHex::for_test(&name, b"abcdef");
+        overwrite(&mut editor, 1, b"X");
+        overwrite(&mut editor, 4, b"YZ");
+        fs::write(&path, contents(&editor)).unwrap();
+        editor.history.mark_saved();
+        save_undo_history(&editor).unwrap();
+
+        let mut reopened = MicroHex::for_test(&name, b"aXcdYZ");
+        assert!(load_undo_history(&mut reopened).unwrap());
+        assert_eq!(reopened.history.latest(), 2);
+        assert!(!reopened.history.is_modified());
+        assert_eq!(reopened.cursor_pos, 4);
+        edit::undo(&mut reopened);
+        edit::undo(&mut reopened);
+        assert_eq!(contents(&reopened), b"abcdef");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn undo_history_for_another_version_is_dropped() {
+        let dir = temp_dir("undo-stale");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"abcdef").unwrap();
+        let name = path.to_string_lossy();
+        let mut editor = MicroHex::for_test(&name, b"abcdef");
+        overwrite(&mut editor, 0, b"X");
+        editor.history.mark_saved();
+        save_undo_history(&editor).unwrap();
+
+        // Same length, different contents
+        fs::write(&path, b"zbcdef").unwrap();
+        let mut reopened = MicroHex::for_test(&name, b"zbcdef");
+        assert!(!load_undo_history(&mut reopened).unwrap());
+        assert!(!sidecar_path(&path, "microhex-undo").exists());
+        assert_eq!(reopened.history.latest(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_undo_history_is_an_error() {
+        let dir = temp_dir("undo-truncated");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"abcdef").unwrap();
+        let name = path.to_string_lossy();
+        let mut editor = MicroHex::for_test(&name, b"abcdef");
+        overwrite(&mut editor, 2, b"Q");
+        save_undo_history(&editor).unwrap();
+
+        let journal_path = sidecar_path(&path, "microhex-undo");
+        let journal = fs::read(&journal_path).unwrap();
+        fs::write(&journal_path, &journal[..journal.len() - 3]).unwrap();
+        assert!(load_undo_history(&mut MicroHex::for_test(&name, b"abcdef")).is_err());
+        fs::write(&journal_path, &journal[..4]).unwrap();
+        assert!(load_undo_history(&mut MicroHex::for_test(&name, b"abcdef")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn swap_files_replay_the_unsaved_edits() {
+        let dir = temp_dir("swap");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"abcdef").unwrap();
+        let name = path.to_string_lossy();
+        assert!(read_swap(&path).unwrap().is_none());
+
+        let mut editor = MicroHex::for_test(&name, b"abcdef");
+        overwrite(&mut editor, 0, b"XY");
+        overwrite(&mut editor, 5, b"Z");
+        write_swap(&editor).unwrap();
+
+        let swap = read_swap(&path).unwrap().unwrap();
+        assert!(swap.matches_file(&path));
+        assert_eq!(swap.changes.len(), 2);
+        let mut recovered = MicroHex::for_test(&name, b"abcdef");
+        for change in swap.changes {
+            edit::replay(&mut recovered, change);
+        }
+        assert_eq!(contents(&recovered), b"XYcdeZ");
+
+        remove_swap(&path).unwrap();
+        assert!(read_swap(&path).unwrap().is_none());
+        remove_swap(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn swap_files_notice_the_file_changing() {
+        let dir = temp_dir("swap-stale");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"abcdef").unwrap();
+        let mut editor = MicroHex::for_test(&path.to_string_lossy(), b"abcdef");
+        overwrite(&mut editor, 0, b"X");
+        write_swap(&editor).unwrap();
+        let swap = read_swap(&path).unwrap().unwrap();
+
+        fs::write(&path, b"abcdefg").unwrap();
+        assert!(!swap.matches_file(&path));
+
+        // Replaced by a file with the same size and modification time, as some tools do when saving
+        fs::write(&path, b"abcdef").unwrap();
+        write_swap(&editor).unwrap();
+        let swap = read_swap(&path).unwrap().unwrap();
+        assert!(swap.matches_file(&path));
+        let replacement = dir.join("replacement");
+        fs::write(&replacement, b"abcdef").unwrap();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        File::options().write(true).open(&replacement).unwrap().set_modified(modified).unwrap();
+        fs::rename(&replacement, &path).unwrap();
+        assert!(!swap.matches_file(&path));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_swap_files_are_errors() {
+        let dir = temp_dir("swap-damaged");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"abcdef").unwrap();
+        let mut editor = MicroHex::for_test(&path.to_string_lossy(), b"abcdef");
+        overwrite(&mut editor, 3, b"Q");
+        write_swap(&editor).unwrap();
+
+        let swap = fs::read(swap_path(&path)).unwrap();
+        for len in [0, 4, 8, 20, swap.len() - 1] {
+            fs::write(swap_path(&path), &swap[..len]).unwrap();
+            assert!(read_swap(&path).is_err(), "truncated to {len} bytes");
+        }
+        let mut old_version = swap.clone();
+        old_version[..8].copy_from_slice(b"MHSWAP2\n");
+        fs::write(swap_path(&path), old_version).unwrap();
+        assert!(read_swap(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
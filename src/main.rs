@@ -1,8 +1,8 @@
 //! Program entry point.
 //!
-//! Handles argument parsing, file loading, config loading, and starts the main editor loop.
+//! Handles argument parsing, file loading, config loading, and starts the main editor loop over every file given.
 
-use std::{fs, env, io, path::PathBuf};
+use std::{env, fs, io::{self, Write}, path::{Path, PathBuf}};
 
 mod editor;
 mod navigation;
@@ -10,36 +10,301 @@ mod display;
 mod edit;
 mod config;
 mod search;
+mod buffer;
+mod paged;
+mod history;
+mod journal;
+mod save;
+mod diff;
+mod goto;
+mod bookmarks;
+mod workspace;
+mod inspector;
+mod encoding;
+mod clipboard;
 
 use editor::{MicroHex};
-use config::AppConfig;
+use buffer::PieceTable;
+use config::{AppConfig, EditorConfig, Endian, Radix, WORD_SIZES};
+use workspace::Workspace;
+use diff::DiffView;
+use encoding::Encoding;
+use clipboard::CopyFormat;
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: microhex <file>");
-        return Ok(());
-    }
+    // Use TOML config file
+    let exe_dir: PathBuf = env::current_exe()?.parent().unwrap().to_path_buf();
+    let config_path = exe_dir.join("config.toml");
+    let mut config = AppConfig::load(config_path.to_str().unwrap());
 
-    let mut bytes = match fs::read(&args[1]) {
-        Ok(data) => data,
+    // Command line options override the config file
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args, &mut config.editor) {
+        Ok(args) => args,
         Err(e) => {
-            eprintln!("Error reading file '{}': {}", &args[1], e);
+            eprintln!("{e}");
+            eprintln!("{USAGE}");
             return Ok(());
         }
     };
 
-    if bytes.is_empty() {
-        bytes.push(0);
+    if args.diff {
+        return run_diff(&args.files[0], &args.files[1], &config);
     }
 
-    // Use TOML config file
-    let exe_dir: PathBuf = env::current_exe()?.parent().unwrap().to_path_buf();
-    let config_path = exe_dir.join("config.toml");
-    let config = AppConfig::load(config_path.to_str().unwrap());
+    let mut buffers = Vec::new();
+    let mut opened = Vec::new();
+    for filename in &args.files {
+        // Two buffers for the same file would overwrite each other's saves and swap file
+        let canonical = fs::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename));
+        if opened.contains(&canonical) {
+            continue;
+        }
+        opened.push(canonical);
+        match open_buffer(filename, &config)? {
+            Opened::Buffer(editor) => buffers.push(*editor),
+            Opened::Unreadable => {} // Already reported; the other files can still be edited
+            Opened::Quit => return Ok(()),
+        }
+    }
+    if buffers.is_empty() {
+        return Ok(());
+    }
 
-    let mut editor = MicroHex::new(args[1].clone(), bytes)?;
-    editor.run(&config.colors)?;
+    let mut workspace = Workspace::new(buffers);
+    workspace.run(&config.colors)?;
+    for editor in &workspace.buffers {
+        if config.editor.persistent_undo
+            && let Err(e) = journal::save_undo_history(editor)
+        {
+            eprintln!("Could not save undo history for '{}': {}", editor.filename, e);
+        }
+        if config.editor.save_bookmarks
+            && let Err(e) = journal::save_bookmarks(editor)
+        {
+            eprintln!("Could not save bookmarks for '{}': {}", editor.filename, e);
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+const USAGE: &str = "\
+Usage: microhex [options] <file>...
+       microhex [options] --diff <old> <new>
+
+Options:
+  --bytes-per-line <N|auto>  Bytes shown on each line, or as many as fit the terminal
+  --group-size <N>           Put an extra space between every N bytes (0 = none)
+  --word-size <1|2|4|8>      Show the hex pane as words of this many bytes
+  --endian <big|little>      Byte order of each word
+  --radix <name>             Show and edit bytes in hex, binary, octal or decimal
+  --encoding <name>          Text pane encoding: ascii, latin1, cp437, cp037, utf8, utf16le or utf16be
+  --copy-format <name>       Put copied bytes on the system clipboard as hex, c, python or base64 (or none)";
+
+/// File names and mode given on the command line.
+struct Args {
+    files: Vec<String>,
+    diff: bool,
+}
+
+/// Split the command line into file names and options, applying the options to `config`.
+fn parse_args(args: &[String], config: &mut EditorConfig) -> Result<Args, String> {
+    let mut files = Vec::new();
+    let mut diff = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut number = || {
+            let value = iter.next().ok_or_else(|| format!("{arg} needs a value"))?;
+            if arg == "--bytes-per-line" && value == "auto" {
+                return Ok(0);
+            }
+            value.parse::<usize>().map_err(|_| format!("Invalid value '{value}' for {arg}"))
+        };
+        match arg.as_str() {
+            "--diff" => diff = true,
+            "--bytes-per-line" => config.bytes_per_line = number()?,
+            "--group-size" => config.group_size = number()?,
+            "--word-size" => {
+                config.word_size = number()?;
+                if !WORD_SIZES.contains(&config.word_size) {
+                    return Err(format!("Word size must be 1, 2, 4 or 8, not {}", config.word_size));
+                }
+            }
+            "--endian" => {
+                config.endian = match iter.next().map(String::as_str) {
+                    Some("big") => Endian::Big,
+                    Some("little") => Endian::Little,
+                    Some(value) => return Err(format!("Invalid value '{value}' for {arg}")),
+                    None => return Err(format!("{arg} needs a value")),
+                }
+            }
+            "--encoding" => {
+                config.encoding = match iter.next().map(String::as_str) {
+                    Some("ascii") => Encoding::Ascii,
+                    Some("latin1") => Encoding::Latin1,
+                    Some("cp437") => Encoding::Cp437,
+                    Some("cp037" | "ebcdic") => Encoding::Cp037,
+                    Some("utf8") => Encoding::Utf8,
+                    Some("utf16le") => Encoding::Utf16le,
+                    Some("utf16be") => Encoding::Utf16be,
+                    Some(value) => return Err(format!("Invalid value '{value}' for {arg}")),
+                    None => return Err(format!("{arg} needs a value")),
+                }
+            }
+            "--radix" => {
+                config.radix = match iter.next().map(String::as_str) {
+                    Some("hex") => Radix::Hex,
+                    Some("binary" | "bin") => Radix::Binary,
+                    Some("octal" | "oct") => Radix::Octal,
+                    Some("decimal" | "dec") => Radix::Decimal,
+                    Some(value) => return Err(format!("Invalid value '{value}' for {arg}")),
+                    None => return Err(format!("{arg} needs a value")),
+                }
+            }
+            "--copy-format" => {
+                config.copy_format = match iter.next().map(String::as_str) {
+                    Some("none") => CopyFormat::None,
+                    Some("hex") => CopyFormat::Hex,
+                    Some("c") => CopyFormat::C,
+                    Some("python") => CopyFormat::Python,
+                    Some("base64") => CopyFormat::Base64,
+                    Some(value) => return Err(format!("Invalid value '{value}' for {arg}")),
+                    None => return Err(format!("{arg} needs a value")),
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option '{arg}'")),
+            _ => files.push(arg.clone()),
+        }
+    }
+    if diff && files.len() != 2 {
+        return Err(String::from("--diff needs exactly two files"));
+    }
+    if files.is_empty() {
+        return Err(String::from("No file given"));
+    }
+    Ok(Args { files, diff })
+}
+
+/// Compare two files side by side. Nothing is edited, so there are no swap files or undo history to deal with.
+fn run_diff(old: &str, new: &str, config: &AppConfig) -> io::Result<()> {
+    let mut panes = Vec::new();
+    for filename in [old, new] {
+        match PieceTable::open(Path::new(filename)) {
+            Ok(bytes) => panes.push(MicroHex::new(filename.to_string(), bytes, config.editor.clone())?),
+            Err(e) => {
+                eprintln!("Error reading file '{}': {}", filename, e);
+                return Ok(());
+            }
+        }
+    }
+    let right = panes.pop().unwrap();
+    let left = panes.pop().unwrap();
+    DiffView::new(left, right).run(&config.colors)
+}
+
+/// What became of one file given on the command line.
+enum Opened {
+    Buffer(Box<MicroHex>),
+    Unreadable, // The error has been printed
+    Quit,       // The user chose to quit at the recovery prompt
+}
+
+/// Load `filename` into a new buffer, offering to recover edits left behind by a session that didn't exit
+/// cleanly.
+fn open_buffer(filename: &str, config: &AppConfig) -> io::Result<Opened> {
+    let path = Path::new(filename);
+    let bytes = match PieceTable::open(path) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", filename, e);
+            return Ok(Opened::Unreadable);
+        }
+    };
+
+    let recovered = match journal::read_swap(path) {
+        Ok(Some(swap)) => match ask_recover(path, &swap)? {
+            Recovery::Recover => Some(swap),
+            Recovery::Discard => {
+                journal::remove_swap(path)?;
+                None
+            }
+            Recovery::Quit => return Ok(Opened::Quit),
+        },
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!("Ignoring unreadable swap file '{}': {}", journal::swap_path(path).display(), e);
+            None
+        }
+    };
+
+    let mut editor = MicroHex::new(filename.to_string(), bytes, config.editor.clone())?;
+    if config.editor.persistent_undo {
+        // A journal that can't be read is just thrown away; it only ever holds history, never unsaved data
+        let _ = journal::load_undo_history(&mut editor);
+    }
+    if config.editor.save_bookmarks
+        && let Err(e) = journal::load_bookmarks(&mut editor)
+    {
+        eprintln!("Ignoring unreadable bookmarks for '{}': {}", filename, e);
+    }
+    if let Some(swap) = recovered {
+        // Recovered edits go on top of the saved state as normal undoable changes
+        for change in swap.changes {
+            edit::replay(&mut editor, change);
+        }
+    }
+    Ok(Opened::Buffer(Box::new(editor)))
+}
+
+enum Recovery {
+    Recover,
+    Discard,
+    Quit,
+}
+
+/// Ask on the plain terminal (before the editor starts) what to do with a leftover swap file.
+fn ask_recover(path: &Path, swap: &journal::Swap) -> io::Result<Recovery> {
+    println!(
+        "Found unsaved changes to '{}' from a previous session ({} change(s) in '{}').",
+        path.display(),
+        swap.changes.len(),
+        journal::swap_path(path).display()
+    );
+    if !swap.matches_file(path) {
+        println!("Warning: the file has been modified since then, so the changes may no longer line up.");
+    }
+    loop {
+        print!("[r]ecover, [d]iscard, [v]iew changes or [q]uit? ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer)? == 0 {
+            return Ok(Recovery::Quit);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "r" => return Ok(Recovery::Recover),
+            "d" => return Ok(Recovery::Discard),
+            "q" => return Ok(Recovery::Quit),
+            "v" => {
+                for change in &swap.changes {
+                    println!(
+                        "  0x{:08X}: [{}] -> [{}]",
+                        change.pos,
+                        hex_preview(&change.old_bytes),
+                        hex_preview(&change.new_bytes)
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Up to 16 bytes as hex, noting how many more were left out.
+fn hex_preview(bytes: &[u8]) -> String {
+    let mut text = bytes.iter().take(16).map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+    if bytes.len() > 16 {
+        text.push_str(&format!(" ... (+{} bytes)", bytes.len() - 16));
+    }
+    text
+}
@@ -27,15 +27,15 @@ pub fn move_up(editor: &mut MicroHex) {
         editor.cursor_pos -= editor.bytes_per_line;
         scroll_to_cursor(editor);
     }
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn move_down(editor: &mut MicroHex) {
-    if editor.cursor_pos + editor.bytes_per_line < editor.bytes.len() {
+    if editor.cursor_pos + editor.bytes_per_line < editor.view_len() {
         editor.cursor_pos += editor.bytes_per_line;
         scroll_to_cursor(editor);
     }
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn move_left(editor: &mut MicroHex) {
@@ -43,27 +43,27 @@ pub fn move_left(editor: &mut MicroHex) {
         editor.cursor_pos -= 1;
         scroll_to_cursor(editor);
     }
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn move_right(editor: &mut MicroHex) {
-    if editor.cursor_pos < editor.bytes.len() - 1 {
+    if editor.cursor_pos < editor.view_len() - 1 {
         editor.cursor_pos += 1;
         scroll_to_cursor(editor);
     } else if editor.mode != EditMode::View {
-        // In edit mode, allow expanding the file
-        editor.bytes.push(0);
+        // In edit mode, allow moving past the end; the new byte only becomes part of the file once edited
+        editor.virtual_len += 1;
         editor.cursor_pos += 1;
         scroll_to_cursor(editor);
     }
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn page_up(editor: &mut MicroHex, factor: usize) {
     let jump = editor.bytes_per_line * editor.lines_per_page * factor;
     editor.offset = editor.offset.saturating_sub(jump);
     editor.cursor_pos = editor.offset;
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn page_down(editor: &mut MicroHex, factor: usize) {
@@ -71,24 +71,22 @@ pub fn page_down(editor: &mut MicroHex, factor: usize) {
     let new_offset = editor.offset + jump;
 
     // Clamp to last full line that can be displayed
-    let max_offset = editor.bytes.len().saturating_sub(1);
+    let max_offset = editor.view_len() - 1;
     let max_line_start = (max_offset / editor.bytes_per_line) * editor.bytes_per_line;
 
     editor.offset = new_offset.min(max_line_start);
     editor.cursor_pos = editor.offset;
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn move_home(editor: &mut MicroHex) {
     editor.cursor_pos = 0;
     scroll_to_cursor(editor);
-    editor.pending_nibble = None;
+    editor.pending_digits = 0;
 }
 
 pub fn move_end(editor: &mut MicroHex) {
-    if !editor.bytes.is_empty() { // If file exists
-        editor.cursor_pos = editor.bytes.len() - 1; // Last valid index
-        scroll_to_cursor(editor);
-    }
-    editor.pending_nibble = None;
+    editor.cursor_pos = editor.view_len() - 1; // Last valid index
+    scroll_to_cursor(editor);
+    editor.pending_digits = 0;
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+//! Lazily loaded, read-only file backend.
+//!
+//! Large files are never read into memory as a whole. `PagedFile` keeps the file open and loads fixed-size
+//! pages on demand, holding a bounded number of them in a small LRU cache. Saving streams straight from the
+//! file instead of going through the cache, so writing out a huge file doesn't evict what's on screen.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+const PAGE_SIZE: usize = 64 * 1024;
+const MAX_CACHED_PAGES: usize = 256; // 16 MiB of cache
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A loaded page and the tick it was last used at.
+struct CachedPage {
+    data: Rc<[u8]>,
+    last_used: u64,
+}
+
+pub struct PagedFile {
+    file: RefCell<File>,
+    len: usize,
+    pages: RefCell<HashMap<usize, CachedPage>>, // Keyed by page index
+    tick: Cell<u64>,
+}
+
+impl PagedFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        Ok(Self {
+            file: RefCell::new(file),
+            len,
+            pages: RefCell::new(HashMap::new()),
+            tick: Cell::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Copy `out.len()` bytes starting at `pos` into `out`, loading pages as needed.
+    /// Pages that fail to read (e.g. the file was truncated underneath us) read as zeros.
+    pub fn read_into(&self, pos: usize, out: &mut [u8]) {
+        let mut done = 0;
+        while done < out.len() {
+            let at = pos + done;
+            let page = self.page(at / PAGE_SIZE);
+            let page_off = at % PAGE_SIZE;
+            let n = (out.len() - done).min(page.len().saturating_sub(page_off));
+            if n == 0 {
+                out[done..].fill(0);
+                return;
+            }
+            out[done..done + n].copy_from_slice(&page[page_off..page_off + n]);
+            done += n;
+        }
+    }
+
+    /// Stream `len` bytes starting at `pos` into `w`, bypassing the page cache.
+    pub fn copy_to<W: Write>(&self, pos: usize, len: usize, w: &mut W) -> io::Result<()> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(pos as u64))?;
+        let mut chunk = vec![0u8; COPY_CHUNK_SIZE.min(len)];
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(chunk.len());
+            file.read_exact(&mut chunk[..n])?;
+            w.write_all(&chunk[..n])?;
+            remaining -= n;
+        }
+        Ok(())
+    }
+
+    fn page(&self, index: usize) -> Rc<[u8]> {
+        let tick = self.tick.get() + 1;
+        self.tick.set(tick);
+
+        let mut pages = self.pages.borrow_mut();
+        if let Some(page) = pages.get_mut(&index) {
+            page.last_used = tick;
+            return page.data.clone();
+        }
+
+        // Evict the least recently used page once the cache is full
+        if pages.len() >= MAX_CACHED_PAGES
+            && let Some(&oldest) = pages.iter().min_by_key(|(_, page)| page.last_used).map(|(i, _)| i)
+        {
+            pages.remove(&oldest);
+        }
+
+        let start = index * PAGE_SIZE;
+        let size = PAGE_SIZE.min(self.len.saturating_sub(start));
+        let data: Rc<[u8]> = self.load(start, size).unwrap_or_else(|_| vec![0; size]).into();
+        pages.insert(index, CachedPage { data: data.clone(), last_used: tick });
+        data
+    }
+
+    fn load(&self, start: usize, size: usize) -> io::Result<Vec<u8>> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(start as u64))?;
+        let mut data = vec![0; size];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
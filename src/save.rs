@@ -0,0 +1,306 @@
+//! Crash-safe file writing.
+//!
+//! New contents are written to a temporary file in the same directory, flushed to disk, given the old file's
+//! permissions and ownership, and only then renamed over the target. A crash or a full disk part way through
+//! leaves the original untouched. Optionally the previous version is kept as a backup.
+//! Also provides `FileStamp`, used to notice when another program changes the file underneath us.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::journal;
+
+/// What to keep of the previous version of a file when it's overwritten.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    #[default]
+    None,
+    Simple,   // A single `file~`, replaced on every save
+    Numbered, // `file.~1~`, `file.~2~`, ... one per save
+}
+
+/// Identity of a file on disk at one point in time. If any part differs later, the file has been
+/// rewritten, replaced or touched by something else.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    inode: u64, // Always 0 where inodes aren't available
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Self {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+            inode: inode(&meta),
+        })
+    }
+
+    /// The stamp as plain numbers for storing in a journal: the length, the modification time in nanoseconds
+    /// since the epoch plus one (0 if it isn't known) and the inode.
+    pub fn to_raw(self) -> [u64; 3] {
+        let since_epoch = self.modified.and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+        let modified = since_epoch.map_or(0, |d| d.as_nanos() as u64 + 1);
+        [self.len, modified, self.inode]
+    }
+
+    pub fn from_raw([len, modified, inode]: [u64; 3]) -> Self {
+        let modified = modified.checked_sub(1).map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos));
+        Self { len, modified, inode }
+    }
+}
+
+#[cfg(unix)]
+fn inode(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Atomically replace `path` with whatever `write` produces, keeping a backup of the old file if asked to.
+/// If `path` is a symlink, the file it points to is replaced and the link left as it is.
+pub fn write_atomic<F>(path: &Path, backup: BackupMode, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    // Renaming over a link would replace the link itself, so work next to the real file instead.
+    // A file that doesn't exist yet (Save As) has nothing to resolve.
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = resolved.as_path();
+    let tmp_path = journal::sidecar_path(path, "microhex-tmp");
+    if let Err(e) = write_temp(path, &tmp_path, write) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    if backup != BackupMode::None && path.exists() {
+        let backup_path = match backup {
+            BackupMode::Numbered => next_numbered_backup(path)?,
+            _ => append_to_name(path, "~"),
+        };
+        if let Err(e) = make_backup(path, &backup_path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    sync_parent_dir(path);
+    Ok(())
+}
+
+fn write_temp<F>(path: &Path, tmp_path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let mut writer = BufWriter::new(File::create(tmp_path)?);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    // Carry the old file's permissions and ownership over to its replacement
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(tmp_path, meta.permissions())?;
+        copy_ownership(&meta, tmp_path);
+    }
+    Ok(())
+}
+
+/// Give `tmp_path` the owner and group of the file it replaces. Only root can give files away, so a
+/// failure here is expected for other users' files and isn't treated as an error.
+#[cfg(unix)]
+fn copy_ownership(meta: &fs::Metadata, tmp_path: &Path) {
+    use std::os::unix::fs::MetadataExt;
+    let _ = std::os::unix::fs::chown(tmp_path, Some(meta.uid()), Some(meta.gid()));
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_meta: &fs::Metadata, _tmp_path: &Path) {}
+
+/// Keep the current contents of `path` at `backup_path`. A hard link costs nothing and keeps the old
+/// contents once the rename replaces `path`; filesystems without hard links get a copy instead.
+fn make_backup(path: &Path, backup_path: &Path) -> io::Result<()> {
+    match fs::remove_file(backup_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    if fs::hard_link(path, backup_path).is_err() {
+        fs::copy(path, backup_path)?;
+    }
+    Ok(())
+}
+
+/// `file.~N~`, with N one higher than any numbered backup already there.
+fn next_numbered_backup(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let prefix = format!("{name}.~");
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut highest = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry_name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some(n) = entry_name
+            .strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix('~'))
+            .and_then(|n| n.parse::<u32>().ok())
+        {
+            highest = highest.max(n);
+        }
+    }
+    Ok(append_to_name(path, &format!(".~{}~", highest + 1)))
+}
+
+fn append_to_name(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Make the rename itself durable. Not all platforms can open directories, so this is best effort.
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// An empty directory of its own for each test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("microhex-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn save(path: &Path, backup: BackupMode, contents: &[u8]) -> io::Result<()> {
+        write_atomic(path, backup, |w| w.write_all(contents))
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> =
+            fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn saving_replaces_the_file_and_leaves_nothing_behind() {
+        let dir = temp_dir("save");
+        let path = dir.join("data.bin");
+        save(&path, BackupMode::None, b"new file").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new file");
+        save(&path, BackupMode::None, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(names(&dir), ["data.bin"]);
+
+        // A failed write keeps the old contents
+        let failed = write_atomic(&path, BackupMode::None, |_| Err(io::Error::other("disk full")));
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert_eq!(names(&dir), ["data.bin"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_rename_removes_the_temp_file() {
+        let dir = temp_dir("save-rename");
+        // A file can't be renamed over a directory that has something in it
+        let path = dir.join("taken");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("inside"), b"x").unwrap();
+        assert!(save(&path, BackupMode::None, b"contents").is_err());
+        assert_eq!(names(&dir), ["taken"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_keeps_the_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("save-mode");
+        let path = dir.join("script.sh");
+        fs::write(&path, b"#!/bin/sh").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+        save(&path, BackupMode::None, b"#!/bin/sh\n").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o751);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn simple_backups_keep_the_previous_version() {
+        let dir = temp_dir("save-simple");
+        let path = dir.join("data.bin");
+        save(&path, BackupMode::Simple, b"one").unwrap();
+        assert_eq!(names(&dir), ["data.bin"]);
+        save(&path, BackupMode::Simple, b"two").unwrap();
+        save(&path, BackupMode::Simple, b"three").unwrap();
+        assert_eq!(names(&dir), ["data.bin", "data.bin~"]);
+        assert_eq!(fs::read(dir.join("data.bin~")).unwrap(), b"two");
+        assert_eq!(fs::read(&path).unwrap(), b"three");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn numbered_backups_count_up_from_the_highest() {
+        let dir = temp_dir("save-numbered");
+        let path = dir.join("data.bin");
+        fs::write(&path, b"one").unwrap();
+        save(&path, BackupMode::Numbered, b"two").unwrap();
+        save(&path, BackupMode::Numbered, b"three").unwrap();
+        assert_eq!(names(&dir), ["data.bin", "data.bin.~1~", "data.bin.~2~"]);
+        assert_eq!(fs::read(dir.join("data.bin.~1~")).unwrap(), b"one");
+        assert_eq!(fs::read(dir.join("data.bin.~2~")).unwrap(), b"two");
+
+        // Gaps and unrelated names are ignored
+        fs::rename(dir.join("data.bin.~2~"), dir.join("data.bin.~7~")).unwrap();
+        fs::write(dir.join("data.bin.~x~"), b"").unwrap();
+        fs::write(dir.join("other.bin.~9~"), b"").unwrap();
+        assert_eq!(next_numbered_backup(&path).unwrap(), dir.join("data.bin.~8~"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_through_a_symlink_updates_its_target() {
+        let dir = temp_dir("save-link");
+        let target = dir.join("real.bin");
+        let link = dir.join("link.bin");
+        fs::write(&target, b"old").unwrap();
+        std::os::unix::fs::symlink("real.bin", &link).unwrap();
+        save(&link, BackupMode::Simple, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(fs::read(dir.join("real.bin~")).unwrap(), b"old");
+        assert_eq!(names(&dir), ["link.bin", "real.bin", "real.bin~"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
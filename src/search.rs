@@ -3,7 +3,12 @@
 //! Provides functions for searching for byte patterns (hex or ASCII) within the file buffer.
 //! Intended for use by the editor event loop to implement search mode, jump to match, and (in the future) search/replace features.
 //!
-//! All search operations are stateless and operate on slices of the file data.
+//! All search operations are stateless and read the file data through the `PieceTable`.
+
+use crate::buffer::PieceTable;
+
+/// How many bytes are read from the buffer at a time while scanning for matches.
+const SEARCH_CHUNK_SIZE: usize = 64 * 1024;
 
 /// Holds the state of an active search session.
 /// Tracks all match positions and current position.
@@ -14,7 +19,7 @@ pub struct SearchState {
 
 impl SearchState {
     /// Create a new search state by finding all matches of pattern in data.
-    pub fn new(data: &[u8], pattern: Vec<u8>) -> Option<Self> {
+    pub fn new(data: &PieceTable, pattern: Vec<u8>) -> Option<Self> {
         let matches = search_all_bytes(data, &pattern);
         if matches.is_empty() {
             None
@@ -99,8 +104,7 @@ pub fn parse_pattern(input: &str) -> Option<Vec<u8>> {
     }
     
     // Check for explicit ASCII/text prefix
-    if trimmed.starts_with("text:") {
-        let text_part = &trimmed[5..];
+    if let Some(text_part) = trimmed.strip_prefix("text:") {
         return Some(text_part.as_bytes().to_vec());
     }
     
@@ -108,7 +112,7 @@ pub fn parse_pattern(input: &str) -> Option<Vec<u8>> {
     let all_hex = trimmed.chars().all(|c| c.is_ascii_hexdigit());
     
     // If all chars are hex AND even length, parse as hex bytes
-    if all_hex && trimmed.len() % 2 == 0 {
+    if all_hex && trimmed.len().is_multiple_of(2) {
         // For each pair of hex digits, convert to a u8 using base 16 (hexadecimal)
         // Example: "4F" -> 79, "fa" -> 250
         let bytes = (0..trimmed.len())
@@ -125,18 +129,25 @@ pub fn parse_pattern(input: &str) -> Option<Vec<u8>> {
 
 /// Search for ALL occurrences of "pattern" in given "data".
 /// Returns a Vec of all starting indices where pattern is found.
-pub fn search_all_bytes(data: &[u8], pattern: &[u8]) -> Vec<usize> {
+///
+/// The buffer is scanned in chunks, each overlapping the previous one by `pattern.len() - 1` bytes,
+/// so matches that straddle a chunk boundary are still found without reading the whole file at once.
+pub fn search_all_bytes(data: &PieceTable, pattern: &[u8]) -> Vec<usize> {
     if pattern.is_empty() || pattern.len() > data.len() {
         return Vec::new();
     }
-    data.windows(pattern.len())
-        .enumerate()
-        .filter_map(|(i, window)| {
-            if window == pattern {
-                Some(i)
-            } else {
-                None
-            }
-        })
-        .collect()
-}
\ No newline at end of file
+    let mut matches = Vec::new();
+    let mut chunk_start = 0;
+    while chunk_start + pattern.len() <= data.len() {
+        let chunk_end = (chunk_start + SEARCH_CHUNK_SIZE + pattern.len() - 1).min(data.len());
+        let chunk = data.read_range(chunk_start..chunk_end);
+        matches.extend(
+            chunk
+                .windows(pattern.len())
+                .enumerate()
+                .filter_map(|(i, window)| if window == pattern { Some(chunk_start + i) } else { None }),
+        );
+        chunk_start += SEARCH_CHUNK_SIZE;
+    }
+    matches
+}
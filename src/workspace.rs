@@ -0,0 +1,171 @@
+//! Several open files and switching between them.
+//!
+//! `Workspace` owns one `MicroHex` per file given on the command line and runs the main TUI loop. Each buffer
+//! keeps its own cursor, undo history and search; the workspace only decides which one receives key presses,
+//! holds the clipboard they share and handles quitting, which has to take every buffer into account.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use crossterm::{
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, ClearType},
+    cursor,
+    event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+};
+
+use crate::{display, journal};
+use crate::config::ColorConfig;
+use crate::editor::{ListAction, MicroHex};
+
+/// How long to wait for input before doing idle work such as updating swap files.
+const IDLE_TICK: Duration = Duration::from_millis(500);
+
+pub struct Workspace {
+    pub buffers: Vec<MicroHex>, // Never empty
+    pub active: usize,          // Index of the buffer being shown and edited
+    clipboard: Vec<u8>,         // Bytes last copied or cut, shared by every buffer
+}
+
+impl Workspace {
+    pub fn new(buffers: Vec<MicroHex>) -> Self {
+        Self { buffers, active: 0, clipboard: Vec::new() }
+    }
+
+    pub fn run(&mut self, colors: &ColorConfig) -> io::Result<()> {
+        execute!(io::stdout(), EnterAlternateScreen, EnableBracketedPaste, cursor::Hide)?;
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::Clear(ClearType::All))?;
+
+        loop {
+            let buffer_list = self.buffer_list();
+            display::draw(&mut self.buffers[self.active], &buffer_list, colors)?;
+
+            // Keep swap files up to date and watch the shown file for outside changes while waiting for input,
+            // redrawing straight away if a reload prompt was shown
+            let mut redraw = false;
+            while !redraw && !event::poll(IDLE_TICK)? {
+                for buffer in &mut self.buffers {
+                    buffer.update_swap();
+                }
+                redraw = self.buffers[self.active].check_external_change(colors)?;
+            }
+            if redraw {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && self.handle_key_event(key, colors)? => break,
+                Event::Paste(text) => self.buffers[self.active].paste_text(&text, &mut self.clipboard, colors)?,
+                _ => {}
+            }
+            self.buffers[self.active].update_swap();
+        }
+
+        // Whatever was pending has now been saved or deliberately thrown away
+        for buffer in &self.buffers {
+            let _ = journal::remove_swap(Path::new(&buffer.filename));
+        }
+        terminal::disable_raw_mode()?;
+        execute!(io::stdout(), cursor::Show, DisableBracketedPaste, LeaveAlternateScreen)?;
+        Ok(())
+    }
+
+    /// Handle keys that concern the whole workspace and pass everything else on to the active buffer.
+    /// Returns true when it's time to quit.
+    fn handle_key_event(&mut self, key: crossterm::event::KeyEvent, colors: &ColorConfig) -> io::Result<bool> {
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char('q') if alt => return self.confirm_quit(colors),
+            KeyCode::Char('.' | '>') if alt => self.switch_to((self.active + 1) % self.buffers.len()),
+            KeyCode::Char(',' | '<') if alt => {
+                self.switch_to((self.active + self.buffers.len() - 1) % self.buffers.len());
+            }
+            KeyCode::Char('b') if alt => {
+                let entries = self.buffer_entries();
+                let footer = "Up/Down: Select   Enter: Switch to buffer   Esc: Close   (* modified)";
+                if let Some(ListAction::Select(index)) =
+                    self.buffers[self.active].select_from_list("Open buffers", &entries, self.active, &[], footer, colors)?
+                {
+                    self.switch_to(index);
+                }
+            }
+            _ => self.buffers[self.active].handle_key_event(key, &mut self.clipboard, colors)?,
+        }
+        Ok(false)
+    }
+
+    fn switch_to(&mut self, index: usize) {
+        self.active = index;
+        // The terminal may have been resized while this buffer was in the background
+        if let Ok((_, rows)) = terminal::size() {
+            self.buffers[index].lines_per_page = display::lines_per_page(rows);
+        }
+    }
+
+    /// Go through every modified buffer asking whether to save it. Returns true if it's fine to quit:
+    /// every modified buffer was either saved or deliberately left unsaved.
+    fn confirm_quit(&mut self, colors: &ColorConfig) -> io::Result<bool> {
+        for index in 0..self.buffers.len() {
+            if !self.buffers[index].modified {
+                continue;
+            }
+            // Show the buffer being asked about
+            self.switch_to(index);
+            let buffer_list = self.buffer_list();
+            display::draw(&mut self.buffers[index], &buffer_list, colors)?;
+            let question = if self.buffers.len() == 1 {
+                String::from("File modified. Save before exit? (y/n/c): ")
+            } else {
+                format!("'{}' modified. Save before exit? (y/n/c): ", self.buffers[index].filename)
+            };
+            let buffer = &mut self.buffers[index];
+            match buffer.prompt(&question)?.map(|ans| ans.to_lowercase()).as_deref() {
+                Some("y") => {
+                    if !buffer.save_reporting_errors(colors)? {
+                        return Ok(false);
+                    }
+                }
+                Some("n") => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Short list of open buffers for the status line, with the active one in brackets.
+    /// Empty when there's only one buffer, since the status line already names it.
+    fn buffer_list(&self) -> String {
+        if self.buffers.len() == 1 {
+            return String::new();
+        }
+        let names: Vec<String> = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let name = Path::new(&buffer.filename)
+                    .file_name()
+                    .map_or_else(|| buffer.filename.clone(), |n| n.to_string_lossy().into_owned());
+                let modified = if buffer.modified { "*" } else { "" };
+                if i == self.active {
+                    format!("[{}:{}{}]", i + 1, name, modified)
+                } else {
+                    format!("{}:{}{}", i + 1, name, modified)
+                }
+            })
+            .collect();
+        names.join(" ")
+    }
+
+    /// One line per buffer for the buffer list popup.
+    fn buffer_entries(&self) -> Vec<String> {
+        self.buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let modified = if buffer.modified { '*' } else { ' ' };
+                format!("{modified} {:<3} {}  ({} bytes)", i + 1, buffer.filename, buffer.bytes.len())
+            })
+            .collect()
+    }
+}